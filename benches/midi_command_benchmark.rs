@@ -19,15 +19,13 @@ fn bench_osc_command(c: &mut Criterion) {
                 motu_commands
                     .clone()
                     .into_iter()
-                    .filter(|cmd| cmd.osc_command().is_some())
-                    .for_each(|cmd| {
-                        cmd.osc_command().unwrap();
-                    }),
+                    .filter_map(|cmd| cmd.osc_command())
+                    .collect::<Vec<_>>(),
             )
         });
     });
 }
-fn create_json_payload_realistic() {
+fn create_json_payload_realistic() -> String {
     let mut commands = Vec::new();
     for i in 0..40 {
         commands.push(MotuCommand::Volume {
@@ -35,9 +33,9 @@ fn create_json_payload_realistic() {
             volume: 0.5,
         });
     }
-    json_payload(&commands);
+    json_payload(&commands)
 }
-fn create_json_payload() {
+fn create_json_payload() -> String {
     let mut commands = Vec::new();
     for i in 0..1000 {
         commands.push(MotuCommand::Volume {
@@ -45,9 +43,9 @@ fn create_json_payload() {
             volume: 0.5,
         });
     }
-    json_payload(&commands);
+    json_payload(&commands)
 }
-fn create_json_payload_vec() {
+fn create_json_payload_vec() -> String {
     let mut commands = Vec::new();
     for i in 0..1000 {
         commands.push(MotuCommand::Volume {
@@ -64,7 +62,7 @@ fn create_json_payload_vec() {
         }
     }
 
-    let s = format!("{{{}}}", pairs.join(", "));
+    format!("{{{}}}", pairs.join(", "))
 }
 fn bench_json_payload(c: &mut Criterion) {
     c.bench_function("bench_json_payload", |b| {
@@ -1,5 +1,7 @@
-use crate::motu;
-use clap::Parser;
+use crate::motu::channel::{ChannelNames, ChannelType};
+use crate::motu::motucommand::parse_level;
+use crate::motu::MotuCommand;
+use clap::{Parser, Subcommand};
 use serde::Deserialize;
 use std::fmt;
 
@@ -87,10 +89,7 @@ impl std::str::FromStr for IpEndpoint {
             return Err(String::from("Invalid IP endpoint format"));
         }
 
-        let address = match parts[0].parse::<IpAddress>() {
-            Ok(value) => value,
-            Err(e) => return Err(e),
-        };
+        let address = parts[0].parse::<IpAddress>()?;
 
         let port = match parts.get(1) {
             Some(port_str) => match port_str.parse::<u16>() {
@@ -142,31 +141,177 @@ impl std::convert::From<&str> for IpEndpoint {
     }
 }
 
+/// Where to reach the interface according to the command line, laid over `[network]`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NetworkArgs {
+    pub ip_address: Option<IpEndpoint>,
+    pub port: Option<u16>,
+}
+
+impl NetworkArgs {
+    /// The endpoint to use instead of `configured`.
+    pub fn endpoint(&self, configured: IpEndpoint) -> IpEndpoint {
+        let mut endpoint = self.ip_address.unwrap_or(configured);
+        if let Some(port) = self.port {
+            endpoint.port = port;
+        }
+        endpoint
+    }
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    #[arg(long, default_value = "./motu_config.toml")]
+    #[arg(long, default_value = "./motu_config.toml", global = true)]
     pub config: String,
-    #[arg(short, long)]
-    pub monitor: Option<bool>,
-    #[arg(short, long)]
-    pub channel: Option<i32>,
-    #[arg(long = "vol")]
-    pub volume: Option<f32>,
-    #[arg(long = "ip")]
+    /// Named profile from the config's [profiles] to use
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    #[arg(long = "ip", global = true)]
     pub ip_address: Option<IpEndpoint>,
-    #[arg(long = "port", default_value = "8000")]
+    /// Port of the interface, overrides the one from --ip or the config
+    #[arg(long = "port", global = true)]
     pub port: Option<u16>,
-    #[arg(short, long)]
-    pub list_channels: bool,
-    #[arg(short, long)]
-    pub aux_channel: Option<i32>,
-    #[arg(short, long)]
-    pub send_amount: Option<f32>,
-    #[arg(short, long)]
-    pub init: bool,
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub verbose: bool,
+    /// Print the OSC messages and HTTP requests instead of sending them
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum Command {
+    /// Set a fader, send or pan, e.g. `set vol OT -6dB`
+    Set {
+        #[command(subcommand)]
+        target: SetTarget,
+    },
+    /// Mute a channel, e.g. `mute group 2`
+    Mute {
+        /// chan, aux or group
+        channel_type: ChannelType,
+        /// Channel index or name from the config
+        channel: String,
+    },
+    /// Unmute a channel, e.g. `unmute chan OT`
+    Unmute {
+        /// chan, aux or group
+        channel_type: ChannelType,
+        /// Channel index or name from the config
+        channel: String,
+    },
+    /// Switch monitoring on or off by unmuting or muting the [monitor_groups]
+    Monitor {
+        /// on or off
+        #[arg(value_parser = parse_switch, action = clap::ArgAction::Set)]
+        state: bool,
+    },
+    /// Set every channel to unity with all sends off and unmute the monitor groups
+    Init,
+    /// List the channels, aux channels and monitor groups of the config
+    List,
+    /// Revert the last change made with motuman
+    Undo,
+    /// Apply the last undone change again
+    Redo,
+    /// Run commands of the mapping DSL, e.g. `run "vol(OT)=-6dB; mute(2)"`
+    Run {
+        /// Commands, several can be given as arguments or separated by `;`
+        #[arg(required = true)]
+        commands: Vec<String>,
+    },
+    /// Run a macro from the config's [macros]
+    Macro { name: String },
+    /// Play a cue file of DSL commands with waits, e.g. `play show.cue --from song2`
+    Play {
+        /// The cue file
+        path: String,
+        /// Label to start playing at
+        #[arg(long)]
+        from: Option<String>,
+    },
+    /// Interactive prompt for the mapping DSL, keeps the connection and history
+    Shell,
+    /// Bridge the MIDI controller from [midi_config] and OSC from [osc_server] to the interface
+    Listen {
+        /// Learn new mappings from the controller and write them to the config file
+        #[arg(long)]
+        learn: bool,
+    },
+    /// Show live levels of the configured channels and aux channels with peak hold
+    Meters,
+    /// Work with the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Convert an oscConfig JSON file into mappings and merge them into the config file
+    Import {
+        /// The oscConfig JSON file
+        path: String,
+        /// Print the merged config instead of writing it
+        #[arg(long)]
+        print: bool,
+    },
+    /// Write an Open Stage Control session with a strip per configured channel
+    Export {
+        /// File to write the session to, printed if not given
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum SetTarget {
+    /// Set the fader of a channel
+    Vol {
+        /// Channel index or name from [channels]
+        channel: String,
+        /// Level in dB (-6dB, -inf) or as gain (0.5), at most +12dB
+        #[arg(allow_hyphen_values = true, value_parser = parse_level)]
+        level: f32,
+    },
+    /// Set the send from a channel to an aux channel
+    Send {
+        /// Channel index or name from [channels]
+        channel: String,
+        /// Aux channel index or name from [aux_channels]
+        aux_channel: String,
+        /// Level in dB (-6dB, -inf) or as gain (0.5), at most +12dB
+        #[arg(allow_hyphen_values = true, value_parser = parse_level)]
+        level: f32,
+    },
+    /// Set the pan of a channel
+    Pan {
+        /// Channel index or name from [channels]
+        channel: String,
+        /// From -1 (left) to 1 (right)
+        #[arg(allow_hyphen_values = true, value_parser = parse_pan)]
+        pan: f32,
+    },
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum ConfigAction {
+    /// Validate the config file and report problems with their line numbers
+    Check,
+}
+
+fn parse_switch(s: &str) -> Result<bool, String> {
+    match s {
+        "on" | "true" => Ok(true),
+        "off" | "false" => Ok(false),
+        _ => Err(format!("expected on or off, got {}", s)),
+    }
+}
+
+fn parse_pan(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(pan) if (-1.0..=1.0).contains(&pan) => Ok(pan),
+        _ => Err(format!("expected a pan from -1 to 1, got {}", s)),
+    }
 }
 
 impl Args {
@@ -178,47 +323,69 @@ impl Args {
         self.config.clone()
     }
 
-    pub fn motu_commands(&self) -> Vec<motu::MotuCommand> {
-        let mut motu_commands: Vec<motu::MotuCommand> = vec![];
-
-        let init = self.init;
-        if init {
-            motu_commands.push(motu::MotuCommand::Init);
-        }
-
-        if let Some(true) = self.monitor {
-            motu_commands.push(motu::MotuCommand::EnableMonitoring);
-        } else if let Some(false) = self.monitor {
-            motu_commands.push(motu::MotuCommand::DisableMonitoring);
-        }
-        let channel = self.channel;
-        let send_to_channel = self.aux_channel;
-        let send_amount = self.send_amount;
-        let volume = self.volume;
-
-        if let (Some(channel), Some(send_to_channel)) = (channel, send_to_channel) {
-            motu_commands.push(motu::MotuCommand::Send {
-                channel: motu::channel::Channel::new(channel, motu::channel::ChannelType::Chan),
-                aux_channel: motu::channel::Channel::new(
-                    send_to_channel,
-                    motu::channel::ChannelType::Aux,
-                ),
-                value: send_amount.unwrap_or(0.0),
-            })
-        }
-
-        if let (Some(channel), Some(volume)) = (channel, volume) {
-            motu_commands.push(motu::MotuCommand::Volume {
-                channel: motu::channel::Channel::new(channel, motu::channel::ChannelType::Chan),
-                volume,
-            })
-        }
-
-        let list_channels = self.list_channels;
-        if list_channels {
-            motu_commands.push(motu::MotuCommand::PrintSettings);
+    pub fn network(&self) -> NetworkArgs {
+        NetworkArgs {
+            ip_address: self.ip_address,
+            port: self.port,
         }
+    }
 
-        motu_commands
+    /// Builds the commands of the subcommand, channels are resolved through `names`.
+    /// Subcommands that don't talk to the interface give no commands.
+    pub fn motu_commands(&self, names: &ChannelNames) -> Result<Vec<MotuCommand>, String> {
+        let motu_commands = match &self.command {
+            Command::Set {
+                target: SetTarget::Vol { channel, level },
+            } => vec![MotuCommand::Volume {
+                channel: names.resolve(channel, ChannelType::Chan)?,
+                volume: *level,
+            }],
+            Command::Set {
+                target:
+                    SetTarget::Send {
+                        channel,
+                        aux_channel,
+                        level,
+                    },
+            } => vec![MotuCommand::Send {
+                channel: names.resolve(channel, ChannelType::Chan)?,
+                aux_channel: names.resolve(aux_channel, ChannelType::Aux)?,
+                value: *level,
+            }],
+            Command::Set {
+                target: SetTarget::Pan { channel, pan },
+            } => vec![MotuCommand::Pan {
+                channel: names.resolve(channel, ChannelType::Chan)?,
+                pan: *pan,
+            }],
+            Command::Mute {
+                channel_type,
+                channel,
+            } => vec![MotuCommand::Mute(names.resolve(channel, *channel_type)?)],
+            Command::Unmute {
+                channel_type,
+                channel,
+            } => vec![MotuCommand::Unmute(names.resolve(channel, *channel_type)?)],
+            Command::Monitor { state: true } => vec![MotuCommand::EnableMonitoring],
+            Command::Monitor { state: false } => vec![MotuCommand::DisableMonitoring],
+            Command::Init => vec![MotuCommand::Init],
+            Command::Macro { name } => vec![MotuCommand::Macro(name.clone())],
+            Command::List => vec![MotuCommand::PrintSettings],
+            Command::Undo => vec![MotuCommand::Undo],
+            Command::Redo => vec![MotuCommand::Redo],
+            Command::Run { commands } => commands
+                .iter()
+                .map(|commands| MotuCommand::parse_list(commands, names))
+                .collect::<Result<Vec<Vec<MotuCommand>>, String>>()?
+                .concat(),
+            Command::Play { .. }
+            | Command::Shell
+            | Command::Listen { .. }
+            | Command::Meters
+            | Command::Config { .. }
+            | Command::Import { .. }
+            | Command::Export { .. } => vec![],
+        };
+        Ok(motu_commands)
     }
 }
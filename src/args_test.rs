@@ -1,138 +1,196 @@
 #[cfg(test)]
 mod tests {
     // use super::*;
-    use crate::args::{Args, IpEndpoint};
+    use crate::args::{Args, Command};
+    use crate::motu::channel::{ChannelNames, ChannelType};
     use crate::*;
+    use clap::Parser;
+
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        Args::try_parse_from(std::iter::once("motuman").chain(args.iter().copied()))
+    }
+
+    fn motu_commands(args: &[&str]) -> Vec<motu::MotuCommand> {
+        parse(args)
+            .unwrap()
+            .motu_commands(&ChannelNames::default())
+            .unwrap()
+    }
 
     #[test]
     fn test_config_file_name() {
-        let args = Args {
-            config: String::from("test_config.toml"),
-            monitor: None,
-            channel: None,
-            volume: None,
-            ip_address: None,
-            port: None,
-            list_channels: false,
-            aux_channel: None,
-            send_amount: None,
-            init: false,
-            verbose: false,
-        };
+        let args = parse(&["--config", "test_config.toml", "init"]).unwrap();
+        assert_eq!(args.config_file_name(), "test_config.toml");
+
+        let args = parse(&["list", "--config", "test_config.toml"]).unwrap();
         assert_eq!(args.config_file_name(), "test_config.toml");
     }
 
     #[test]
     fn test_motu_commands() {
-        let args = Args {
-            config: String::from("test_config.toml"),
-            monitor: Some(true),
-            channel: Some(1),
-            volume: Some(0.5),
-            ip_address: None,
-            port: Some(8000),
-            list_channels: true,
-            aux_channel: Some(2),
-            send_amount: Some(0.3),
-            init: true,
-            verbose: false,
-        };
-        let mut expected_commands = vec![
-            motu::MotuCommand::Init,
-            motu::MotuCommand::EnableMonitoring,
-            motu::MotuCommand::Send {
+        assert_eq!(motu_commands(&["init"]), vec![motu::MotuCommand::Init]);
+        assert_eq!(
+            motu_commands(&["monitor", "on"]),
+            vec![motu::MotuCommand::EnableMonitoring]
+        );
+        assert_eq!(
+            motu_commands(&["monitor", "off"]),
+            vec![motu::MotuCommand::DisableMonitoring]
+        );
+        assert_eq!(
+            motu_commands(&["list"]),
+            vec![motu::MotuCommand::PrintSettings]
+        );
+        assert_eq!(
+            motu_commands(&["set", "send", "1", "2", "0.3"]),
+            vec![motu::MotuCommand::Send {
                 channel: motu::channel::Channel::new(1, motu::channel::ChannelType::Chan),
                 aux_channel: motu::channel::Channel::new(2, motu::channel::ChannelType::Aux),
                 value: 0.3,
-            },
-            motu::MotuCommand::Volume {
+            }]
+        );
+        assert_eq!(
+            motu_commands(&["set", "vol", "1", "0.5"]),
+            vec![motu::MotuCommand::Volume {
                 channel: motu::channel::Channel::new(1, motu::channel::ChannelType::Chan),
                 volume: 0.5,
-            },
-            motu::MotuCommand::PrintSettings,
-        ];
-        println!("Commands: \n{:?}", args.motu_commands());
-        println!("Expected commands: \n{:?}", expected_commands);
-        assert_eq!(args.motu_commands(), expected_commands);
-
-        let args = Args {
-            config: String::from("test_config.toml"),
-            monitor: Some(false),
-            channel: Some(1),
-            volume: Some(0.5),
-            ip_address: None,
-            port: Some(8000),
-            list_channels: false,
-            aux_channel: Some(2),
-            send_amount: Some(0.3),
-            init: false,
-            verbose: false,
-        };
-        expected_commands = vec![
-            motu::MotuCommand::DisableMonitoring,
-            motu::MotuCommand::Send {
-                channel: motu::channel::Channel::new(1, motu::channel::ChannelType::Chan),
-                aux_channel: motu::channel::Channel::new(2, motu::channel::ChannelType::Aux),
-                value: 0.3,
-            },
-            motu::MotuCommand::Volume {
+            }]
+        );
+        assert_eq!(
+            motu_commands(&["set", "pan", "1", "-0.5"]),
+            vec![motu::MotuCommand::Pan {
                 channel: motu::channel::Channel::new(1, motu::channel::ChannelType::Chan),
-                volume: 0.5,
-            },
-        ];
-        println!("Commands: \n{:?}", args.motu_commands());
-        println!("Expected commands: \n{:?}", expected_commands);
-        assert_eq!(args.motu_commands(), expected_commands);
+                pan: -0.5,
+            }]
+        );
+        assert_eq!(
+            motu_commands(&["mute", "group", "2"]),
+            vec![motu::MotuCommand::Mute(motu::channel::Channel::new(
+                2,
+                motu::channel::ChannelType::Group
+            ))]
+        );
+        assert_eq!(
+            motu_commands(&["run", "vol(1)=0dB; mute(2)", "monitor(on)"]),
+            vec![
+                motu::MotuCommand::Volume {
+                    channel: motu::channel::Channel::new(1, motu::channel::ChannelType::Chan),
+                    volume: 1.0,
+                },
+                motu::MotuCommand::Mute(motu::channel::Channel::new(
+                    2,
+                    motu::channel::ChannelType::Chan
+                )),
+                motu::MotuCommand::EnableMonitoring,
+            ]
+        );
+        assert_eq!(motu_commands(&["listen"]), vec![]);
     }
 
     #[test]
-    fn test_ip_address_only() {
-        let args = Args {
-            config: String::from("test_config.toml"),
-            monitor: None,
-            channel: None,
-            volume: None,
-            ip_address: Some(IpEndpoint::from("192.168.1.2")),
-            port: Some(8000),
-            list_channels: false,
-            aux_channel: None,
-            send_amount: None,
-            init: false,
-            verbose: false,
+    fn test_set_levels() {
+        let volume = |level: &str| match motu_commands(&["set", "vol", "1", level])[0] {
+            motu::MotuCommand::Volume { volume, .. } => volume,
+            _ => unreachable!(),
         };
+        assert_eq!(volume("0dB"), 1.0);
+        assert_eq!(volume("-inf"), 0.0);
+        assert!((volume("-6dB") - 0.501).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_invalid_commands() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["set", "vol", "1"]).is_err());
+        assert!(parse(&["set", "vol", "1", "13dB"]).is_err());
+        assert!(parse(&["set", "send", "1", "0.5"]).is_err());
+        assert!(parse(&["set", "pan", "1", "2"]).is_err());
+        assert!(parse(&["mute", "2"]).is_err());
+        assert!(parse(&["monitor", "maybe"]).is_err());
+        assert!(parse(&["run"]).is_err());
+        assert!(parse(&["run", "vol(1)=loud"])
+            .unwrap()
+            .motu_commands(&ChannelNames::default())
+            .is_err());
+        // without a value the fader would jump to a placeholder
+        assert!(parse(&["run", "vol(2)"])
+            .unwrap()
+            .motu_commands(&ChannelNames::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_motu_commands_by_name() {
+        let mut names = ChannelNames::default();
+        names.insert(ChannelType::Chan, 2, "OT");
+        names.insert(ChannelType::Aux, 6, "Space");
+        let args = parse(&["set", "send", "ot", "SPACE", "0.5"]).unwrap();
+        assert_eq!(
+            args.motu_commands(&names),
+            Ok(vec![motu::MotuCommand::Send {
+                channel: motu::channel::Channel::new(2, ChannelType::Chan),
+                aux_channel: motu::channel::Channel::new(6, ChannelType::Aux),
+                value: 0.5,
+            }])
+        );
+
+        names.insert(ChannelType::Chan, 4, "Ot");
+        assert!(args.motu_commands(&names).is_err());
+    }
+
+    #[test]
+    fn test_ip_address_only() {
+        let args = parse(&["--ip", "192.168.1.2", "init"]).unwrap();
         assert_eq!(args.ip_address.unwrap().address.to_string(), "192.168.1.2");
+        assert_eq!(args.command, Command::Init);
 
-        let args = Args {
-            config: String::from("test_config.toml"),
-            monitor: None,
-            channel: None,
-            volume: None,
-            ip_address: Some(IpEndpoint::from("192.168.1.2:9000")),
-            port: Some(8000),
-            list_channels: false,
-            aux_channel: None,
-            send_amount: None,
-            init: false,
-            verbose: false,
-        };
+        let args = parse(&["--ip", "192.168.1.2:9000", "init"]).unwrap();
         assert_eq!(args.ip_address.unwrap().address.to_string(), "192.168.1.2");
 
-        let args = Args {
-            config: String::from("test_config.toml"),
-            monitor: None,
-            channel: None,
-            volume: None,
-            ip_address: Some(IpEndpoint::from("192.168.256.2:9000")),
-            port: Some(8000),
-            list_channels: false,
-            aux_channel: None,
-            send_amount: None,
-            init: false,
-            verbose: false,
-        };
+        let args = parse(&["--ip", "192.168.256.2:9000", "init"]).unwrap();
         assert_ne!(
             args.ip_address.unwrap().address.to_string(),
             "192.168.256.2"
         );
     }
+
+    #[test]
+    fn test_port() {
+        let configured = crate::args::IpEndpoint::from("192.168.1.2:8000");
+        let args = parse(&["init", "--port", "9"]).unwrap();
+        assert_eq!(
+            args.network().endpoint(configured).to_string(),
+            "192.168.1.2:9"
+        );
+
+        let args = parse(&["--ip", "10.0.0.1:9000", "--port", "9", "init"]).unwrap();
+        assert_eq!(
+            args.network().endpoint(configured).to_string(),
+            "10.0.0.1:9"
+        );
+
+        let args = parse(&["init"]).unwrap();
+        assert_eq!(args.network().endpoint(configured), configured);
+    }
+
+    #[test]
+    fn test_dry_run() {
+        assert!(!parse(&["init"]).unwrap().dry_run);
+        assert!(parse(&["--dry-run", "init"]).unwrap().dry_run);
+        assert!(
+            parse(&["macro", "vocal_check", "--dry-run"])
+                .unwrap()
+                .dry_run
+        );
+    }
+
+    #[test]
+    fn test_meters() {
+        let args = parse(&["meters", "--ip", "192.168.1.2"]).unwrap();
+        assert_eq!(args.command, Command::Meters);
+        assert!(args
+            .motu_commands(&ChannelNames::default())
+            .unwrap()
+            .is_empty());
+    }
 }
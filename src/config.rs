@@ -1,12 +1,23 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
 
+use crate::midi::mapping::MidiMapping;
+use crate::midi::miditype::MidiType;
+use crate::midi::throttle::ThrottlePolicy;
+use crate::motu::channel::{ChannelNames, ChannelType};
+use crate::motu::transport::TransportPolicy;
 use crate::motu::MotuCommand;
 
-use crate::args::IpEndpoint;
+use crate::args::{IpEndpoint, NetworkArgs};
+
+pub mod check;
+pub mod edit;
+pub mod profile;
 
 #[derive(Debug, Deserialize)]
 pub enum MidiCommand {
@@ -19,54 +30,412 @@ pub enum MidiCommand {
     MonitorOff,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MidiConfig {
     pub input: String,
     pub output: String,
     pub midi_channel: u8,
+    #[serde(default)]
+    pub throttle: ThrottlePolicy,
+    /// Minimum number of milliseconds between two batches sent to the interface.
+    #[serde(default = "default_send_interval")]
+    pub send_interval: u64,
+}
+
+fn default_send_interval() -> u64 {
+    10
+}
+
+/// Where `motuman listen` takes OSC from tablets or other software.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OscServerConfig {
+    pub port: u16,
+    #[serde(default)]
+    pub throttle: ThrottlePolicy,
 }
 
 #[serde_as]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct NetworkConfig {
     #[serde_as(as = "DisplayFromStr")]
     pub ip_address: IpEndpoint,
+    /// How batches are sent, OSC or the HTTP datastore.
+    #[serde(default)]
+    pub transport: TransportPolicy,
 }
 
 #[serde_as]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
     // #[serde_as(as = "DisplayFromStr")]
     #[serde(skip)]
     pub ip_address: IpEndpoint,
     pub network: NetworkConfig,
-    #[serde_as(as = "HashMap<DisplayFromStr, _>")]
-    pub aux_channels: HashMap<usize, String>,
-    #[serde_as(as = "HashMap<DisplayFromStr, _>")]
-    pub channels: HashMap<usize, String>,
-    #[serde_as(as = "HashMap<DisplayFromStr, _>")]
-    pub monitor_groups: HashMap<usize, String>,
+    #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
+    pub aux_channels: BTreeMap<usize, String>,
+    #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
+    pub channels: BTreeMap<usize, String>,
+    #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
+    pub monitor_groups: BTreeMap<usize, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub midi_config: Option<MidiConfig>,
-    #[serde_as(as = "HashMap<DisplayFromStr, DisplayFromStr>")]
-    pub midi_mapping_cc: HashMap<usize, MotuCommand>,
-    #[serde_as(as = "HashMap<DisplayFromStr, DisplayFromStr>")]
-    pub midi_mapping_note_on: HashMap<usize, MotuCommand>,
-    #[serde_as(as = "HashMap<DisplayFromStr, DisplayFromStr>")]
-    pub midi_mapping_note_off: HashMap<usize, MotuCommand>,
+    #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
+    pub midi_mapping_cc: BTreeMap<usize, MidiMapping>,
+    #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
+    pub midi_mapping_note_on: BTreeMap<usize, MidiMapping>,
+    #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
+    pub midi_mapping_note_off: BTreeMap<usize, MidiMapping>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub osc_server: Option<OscServerConfig>,
+    /// Mappings for OSC addresses below `/motuman/`, e.g. `"fader1" = "vol(OT)"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub osc_mapping: BTreeMap<String, MidiMapping>,
+    /// Named lists of DSL commands, run with `macro(name)`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub macros: BTreeMap<String, Vec<String>>,
+    /// Files the config was loaded from, the main file first.
+    #[serde(skip)]
+    pub files: Vec<PathBuf>,
 }
 
 impl Config {
-    pub fn build(file_name: String, arg_ip: Option<IpEndpoint>) -> Result<Config, Box<dyn Error>> {
-        let config_file = fs::read_to_string(dbg!(file_name))?;
+    pub fn build(
+        file_name: String,
+        network: NetworkArgs,
+        profile: Option<&str>,
+    ) -> Result<Config, Box<dyn Error>> {
+        let loaded = profile::load(&file_name, profile)?;
+
+        let mut config = Config::from_table(loaded.table)?;
+        config.files = loaded.files;
+        config.ip_address = network.endpoint(config.network.ip_address);
 
-        let mut config: Config = toml::from_str(&config_file)?;
+        Ok(config)
+    }
 
-        if let Some(ip) = arg_ip {
-            config.ip_address = ip;
-        } else {
-            config.ip_address = config.network.ip_address;
+    /// Parses a config file, channel names in the mapping tables are resolved to indices.
+    pub fn parse(source: &str) -> Result<Config, Box<dyn Error>> {
+        Config::from_table(toml::from_str(source)?)
+    }
+
+    /// The mappings for a MIDI message type, with includes and the profile applied.
+    pub fn mappings(&self, midi_type: &MidiType) -> Option<&BTreeMap<usize, MidiMapping>> {
+        match midi_type {
+            MidiType::CC => Some(&self.midi_mapping_cc),
+            MidiType::NoteOn => Some(&self.midi_mapping_note_on),
+            MidiType::NoteOff => Some(&self.midi_mapping_note_off),
+            MidiType::Undefined => None,
         }
+    }
 
+    fn from_table(mut table: toml::Table) -> Result<Config, Box<dyn Error>> {
+        let names = NameTables::deserialize(toml::Value::Table(table.clone()))?.channel_names();
+        for table_name in MAPPING_TABLES.into_iter().chain(["osc_mapping"]) {
+            let Some(mappings) = table.get_mut(table_name).and_then(|t| t.as_table_mut()) else {
+                continue;
+            };
+            for (key, mapping) in mappings.iter_mut() {
+                resolve_mapping_names(mapping, &names)
+                    .map_err(|err| format!("[{}] {}: {}", table_name, key, err))?;
+            }
+        }
+        let config: Config = table.try_into()?;
+        config.macros()?;
         Ok(config)
     }
+
+    /// Parses the `[macros]`, every `macro(name)` in them and in the mappings has
+    /// to name a macro, and macros can't call themselves.
+    pub fn macros(&self) -> Result<BTreeMap<String, Vec<MotuCommand>>, String> {
+        let names = self.channel_names();
+        let mut macros = BTreeMap::new();
+        for (name, commands) in &self.macros {
+            let commands = commands
+                .iter()
+                .map(|command| MotuCommand::parse_assignment(command, &names))
+                .collect::<Result<Vec<MotuCommand>, String>>()
+                .map_err(|err| format!("[macros] {}: {}", name, err))?;
+            macros.insert(name.clone(), commands);
+        }
+
+        let mappings = [
+            &self.midi_mapping_cc,
+            &self.midi_mapping_note_on,
+            &self.midi_mapping_note_off,
+        ];
+        for command in mappings
+            .iter()
+            .flat_map(|mappings| mappings.values())
+            .chain(self.osc_mapping.values())
+            .map(|mapping| &mapping.command)
+        {
+            if let MotuCommand::Macro(name) = command {
+                if !macros.contains_key(name) {
+                    return Err(format!("Unknown macro {} in a mapping", name));
+                }
+            }
+        }
+        for name in macros.keys() {
+            check_macro(name, &macros, &mut vec![])?;
+        }
+        Ok(macros)
+    }
+
+    /// Writes the config back as TOML. Mapping commands are written in the DSL with
+    /// channel indices, includes and profiles are not preserved.
+    pub fn to_toml(&self) -> Result<String, Box<dyn Error>> {
+        Ok(toml::to_string(self)?)
+    }
+
+    pub fn save(&self, file_name: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(file_name, self.to_toml()?)?;
+        Ok(())
+    }
+
+    pub fn channel_names(&self) -> ChannelNames {
+        channel_names(&self.channels, &self.aux_channels, &self.monitor_groups)
+    }
+}
+
+const MAPPING_TABLES: [&str; 3] = [
+    "midi_mapping_cc",
+    "midi_mapping_note_on",
+    "midi_mapping_note_off",
+];
+
+/// Just the name tables of a config, read before the mappings that refer to them.
+#[serde_as]
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct NameTables {
+    #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
+    #[serde(default)]
+    aux_channels: BTreeMap<usize, String>,
+    #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
+    #[serde(default)]
+    channels: BTreeMap<usize, String>,
+    #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
+    #[serde(default)]
+    monitor_groups: BTreeMap<usize, String>,
+}
+
+impl NameTables {
+    pub(crate) fn channel_names(&self) -> ChannelNames {
+        channel_names(&self.channels, &self.aux_channels, &self.monitor_groups)
+    }
+}
+
+fn channel_names(
+    channels: &BTreeMap<usize, String>,
+    aux_channels: &BTreeMap<usize, String>,
+    monitor_groups: &BTreeMap<usize, String>,
+) -> ChannelNames {
+    let mut names = ChannelNames::default();
+    for (channel_type, table) in [
+        (ChannelType::Chan, channels),
+        (ChannelType::Aux, aux_channels),
+        (ChannelType::Group, monitor_groups),
+    ] {
+        for (index, name) in table {
+            names.insert(channel_type, *index, name);
+        }
+    }
+    names
+}
+
+/// Follows the macros called by `name`, failing on unknown macros and cycles.
+fn check_macro<'a>(
+    name: &'a str,
+    macros: &'a BTreeMap<String, Vec<MotuCommand>>,
+    stack: &mut Vec<&'a str>,
+) -> Result<(), String> {
+    if stack.contains(&name) {
+        return Err(format!("Macro {} calls itself", name));
+    }
+    let commands = macros
+        .get(name)
+        .ok_or_else(|| format!("Unknown macro {}", name))?;
+    stack.push(name);
+    for command in commands {
+        if let MotuCommand::Macro(called) = command {
+            check_macro(called, macros, stack)?;
+        }
+    }
+    stack.pop();
+    Ok(())
+}
+
+/// Rewrites the command of a mapping entry with channel names replaced by indices.
+pub(crate) fn resolve_mapping_names(
+    mapping: &mut toml::Value,
+    names: &ChannelNames,
+) -> Result<(), String> {
+    let command = match mapping {
+        toml::Value::String(command) => command,
+        toml::Value::Table(table) => match table.get_mut("command") {
+            Some(toml::Value::String(command)) => command,
+            _ => return Ok(()),
+        },
+        _ => return Ok(()),
+    };
+    *command = MotuCommand::parse(command, names)?.to_string();
+    Ok(())
+}
+
+/// Something built from the config that is rebuilt when the config changes. It is
+/// switched in two steps, so several of them take a new config together or not at all.
+pub trait Reload: Send {
+    /// What `apply` needs to switch to a new config.
+    type Prepared;
+
+    /// Builds everything that can fail, without touching the running state.
+    fn prepare(&self, config: &Config) -> Result<Self::Prepared, Box<dyn Error>>;
+
+    fn apply(&mut self, prepared: Self::Prepared);
+}
+
+/// Notices changes to the files of a config by polling their modification times.
+pub struct ConfigWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl ConfigWatcher {
+    pub fn new(files: &[PathBuf]) -> ConfigWatcher {
+        let mut watcher = ConfigWatcher { files: vec![] };
+        watcher.watch(files);
+        watcher
+    }
+
+    /// Replaces the watched files, e.g. after a reload changed the includes.
+    pub fn watch(&mut self, files: &[PathBuf]) {
+        self.files = files
+            .iter()
+            .map(|file| (file.clone(), modified(file)))
+            .collect();
+    }
+
+    /// Returns `true` once for every change of the files since the last call.
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        for (file, last_modified) in self.files.iter_mut() {
+            let modified = modified(file);
+            if modified != *last_modified {
+                *last_modified = modified;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+fn modified(file: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+/// Name of the config table holding the mappings for a MIDI message type.
+pub fn mapping_table(midi_type: &MidiType) -> Option<&'static str> {
+    match midi_type {
+        MidiType::CC => Some("midi_mapping_cc"),
+        MidiType::NoteOn => Some("midi_mapping_note_on"),
+        MidiType::NoteOff => Some("midi_mapping_note_off"),
+        MidiType::Undefined => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watcher_notices_changes() {
+        let file = std::env::temp_dir().join(format!("motuman_watch_{}.toml", std::process::id()));
+        fs::write(&file, "[channels]").unwrap();
+        let mut watcher = ConfigWatcher::new(std::slice::from_ref(&file));
+        assert!(!watcher.changed());
+
+        let modified = modified(&file).unwrap() + std::time::Duration::from_secs(10);
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+
+        fs::remove_file(&file).unwrap();
+        assert!(watcher.changed());
+    }
+
+    #[test]
+    fn test_save_round_trip() {
+        let source = r#"
+            [network]
+            ip_address = "127.0.0.1:8000"
+
+            [channels]
+            2 = "OT"
+            10 = "Syntakt"
+
+            [aux_channels]
+            4 = "H90"
+
+            [monitor_groups]
+
+            [midi_config]
+            input = "in"
+            output = "out"
+            midi_channel = 1
+            throttle = { fixed_rate = 20 }
+
+            [midi_mapping_cc]
+            34 = "send(OT,H90)"
+            35 = { command = "vol(10)", curve = "linear", range = [0.0, 0.5] }
+
+            [midi_mapping_note_on]
+            60 = { command = "mute(2)", mode = "toggle" }
+
+            [midi_mapping_note_off]
+
+            [macros]
+            vocal_check = ["monitor(off)", "vol(OT)=-inf"]
+            "#;
+        let config = Config::parse(source).unwrap();
+        let saved = config.to_toml().unwrap();
+        assert!(saved.find("2 = \"OT\"").unwrap() < saved.find("10 = \"Syntakt\"").unwrap());
+        assert!(saved.contains("34 = \"send(2,4)\""), "{}", saved);
+
+        let reloaded = Config::parse(&saved).unwrap();
+        assert_eq!(reloaded.midi_mapping_cc, config.midi_mapping_cc);
+        assert_eq!(reloaded.midi_mapping_note_on, config.midi_mapping_note_on);
+        assert_eq!(reloaded.to_toml().unwrap(), saved);
+        assert_eq!(reloaded.macros, config.macros);
+    }
+
+    #[test]
+    fn test_macros() {
+        let source = |macros: &str| {
+            format!(
+                "[network]\nip_address = \"127.0.0.1:8000\"\n[channels]\n2 = \"OT\"\n\
+                 [aux_channels]\n[monitor_groups]\n[midi_mapping_cc]\n1 = \"macro(check)\"\n\
+                 [midi_mapping_note_on]\n[midi_mapping_note_off]\n[macros]\n{}",
+                macros
+            )
+        };
+        let config = Config::parse(&source(
+            "check = [\"monitor(off)\", \"macro(quiet)\"]\nquiet = [\"vol(OT)=-inf\"]",
+        ))
+        .unwrap();
+        let macros = config.macros().unwrap();
+        assert_eq!(
+            macros["check"][1],
+            MotuCommand::Macro(String::from("quiet"))
+        );
+        assert_eq!(macros["quiet"][0].to_string(), "vol(2)");
+
+        assert!(Config::parse(&source("quiet = [\"vol(OT)=-inf\"]")).is_err());
+        assert!(Config::parse(&source("check = [\"macro(check)\"]")).is_err());
+        assert!(Config::parse(&source("check = [\"vol(Nope)\"]")).is_err());
+        // macros are run as they are, a fader needs its level
+        assert!(Config::parse(&source("check = [\"vol(OT)\"]")).is_err());
+    }
 }
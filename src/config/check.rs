@@ -0,0 +1,403 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+
+use serde::de::{IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use toml::{Spanned, Table, Value};
+
+use super::{resolve_mapping_names, Config, NameTables};
+use crate::midi::mapping::MidiMapping;
+use crate::midi::miditype::MidiType;
+use crate::motu::channel::{Channel, ChannelNames, ChannelType};
+use crate::motu::MotuCommand;
+
+/// A problem found in a config file.
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    /// 1-based line in the config file, if the problem can be pinned to one.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Validates a config file, collecting every problem instead of stopping at the first.
+pub fn check(source: &str) -> Vec<Diagnostic> {
+    match toml::from_str(source) {
+        Ok(table) => check_table(source, table),
+        Err(err) => vec![Diagnostic {
+            line: err.span().map(|span| line_of_offset(source, span.start)),
+            message: err.message().to_string(),
+        }],
+    }
+}
+
+/// Validates an already merged config table, `source` is the main file and is
+/// only used to find line numbers.
+pub fn check_table(source: &str, table: Table) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        key_lines: key_lines(source),
+        diagnostics: vec![],
+        names: HashMap::new(),
+    };
+
+    let channel_names = NameTables::deserialize(Value::Table(table.clone()))
+        .unwrap_or_default()
+        .channel_names();
+    checker.names = [
+        (ChannelType::Chan, "channels"),
+        (ChannelType::Aux, "aux_channels"),
+        (ChannelType::Group, "monitor_groups"),
+    ]
+    .into_iter()
+    .map(|(channel_type, name)| (channel_type, checker.indices(&table, name)))
+    .collect();
+
+    if let Some(midi_channel) = table
+        .get("midi_config")
+        .and_then(|midi_config| midi_config.get("midi_channel"))
+        .and_then(Value::as_integer)
+    {
+        if !(1..=16).contains(&midi_channel) {
+            let line = checker.key_line("midi_config", "midi_channel");
+            checker.report(
+                line,
+                format!("midi_channel {} is not in 1..=16", midi_channel),
+            );
+        }
+    }
+
+    let mut resolved: HashMap<(u8, u8), String> = HashMap::new();
+    for midi_type in [MidiType::CC, MidiType::NoteOn, MidiType::NoteOff] {
+        let table_name = super::mapping_table(&midi_type).unwrap_or_default();
+        let Some(mappings) = table.get(table_name).and_then(Value::as_table) else {
+            continue;
+        };
+        let status = u8::from(midi_type);
+        for (key, value) in mappings {
+            let line = checker.key_line(table_name, key);
+            let number = match key.parse::<usize>() {
+                Ok(number) => number,
+                Err(_) => {
+                    checker.report(
+                        line,
+                        format!("[{}] key {} is not a number", table_name, key),
+                    );
+                    continue;
+                }
+            };
+            if number > 127 {
+                checker.report(
+                    line,
+                    format!(
+                        "[{}] {} is out of the MIDI range 0..=127 and would be read as {}",
+                        table_name, number, number as u8
+                    ),
+                );
+            }
+            if let Some(other) = resolved.insert((status, number as u8), key.clone()) {
+                checker.report(
+                    line,
+                    format!(
+                        "[{}] {} and {} both map MIDI number {}",
+                        table_name, other, key, number as u8
+                    ),
+                );
+            }
+
+            checker.mapping(table_name, key, value, &channel_names);
+        }
+    }
+
+    if let Some(mappings) = table.get("osc_mapping").and_then(Value::as_table) {
+        for (key, value) in mappings {
+            checker.mapping("osc_mapping", key, value, &channel_names);
+        }
+    }
+
+    if let Some(macros) = table.get("macros").and_then(Value::as_table) {
+        for (name, commands) in macros {
+            let line = checker.key_line("macros", name);
+            // the type of the list is left to the full parse below
+            for command in commands
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                match MotuCommand::parse_assignment(command, &channel_names) {
+                    Ok(command) => checker.channels(line, "macros", name, &command.channels()),
+                    Err(err) => checker.report(line, format!("[macros] {}: {}", name, err)),
+                }
+            }
+        }
+    }
+
+    if checker.diagnostics.is_empty() {
+        if let Err(err) = Config::from_table(table) {
+            checker.report(None, err.to_string());
+        }
+    }
+    checker
+        .diagnostics
+        .sort_by_key(|diagnostic| diagnostic.line);
+    checker.diagnostics
+}
+
+struct Checker {
+    key_lines: HashMap<String, HashMap<String, usize>>,
+    diagnostics: Vec<Diagnostic>,
+    /// The indices defined in each channel name table.
+    names: HashMap<ChannelType, HashSet<usize>>,
+}
+
+impl Checker {
+    fn report(&mut self, line: Option<usize>, message: String) {
+        self.diagnostics.push(Diagnostic { line, message });
+    }
+
+    /// Checks the command and options of a mapping and the channels it uses.
+    fn mapping(&mut self, table_name: &str, key: &str, value: &Value, names: &ChannelNames) {
+        let line = self.key_line(table_name, key);
+        let mut value = value.clone();
+        if let Err(err) = resolve_mapping_names(&mut value, names) {
+            self.report(line, format!("[{}] {}: {}", table_name, key, err));
+            return;
+        }
+        match MidiMapping::deserialize(value) {
+            Ok(mapping) => self.channels(line, table_name, key, &mapping.command.channels()),
+            Err(err) => self.report(
+                line,
+                format!("[{}] {}: {}", table_name, key, err.message().trim()),
+            ),
+        }
+    }
+
+    /// Reports channels whose index isn't in the name table of their type.
+    fn channels(&mut self, line: Option<usize>, table_name: &str, key: &str, channels: &[Channel]) {
+        for channel in channels {
+            let known = self
+                .names
+                .get(channel.channel_type())
+                .is_some_and(|indices| {
+                    usize::try_from(channel.channel_number())
+                        .is_ok_and(|index| indices.contains(&index))
+                });
+            if !known {
+                self.report(
+                    line,
+                    format!(
+                        "[{}] {}: {} is not defined in [{}]",
+                        table_name,
+                        key,
+                        channel,
+                        channel_table(channel.channel_type())
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Collects the indices of a channel name table, reporting keys that aren't numbers.
+    fn indices(&mut self, table: &Table, name: &str) -> HashSet<usize> {
+        let mut indices = HashSet::new();
+        let Some(entries) = table.get(name).and_then(Value::as_table) else {
+            return indices;
+        };
+        for key in entries.keys() {
+            match key.parse::<usize>() {
+                Ok(index) => {
+                    indices.insert(index);
+                }
+                Err(_) => {
+                    let line = self.key_line(name, key);
+                    self.report(line, format!("[{}] key {} is not a number", name, key));
+                }
+            }
+        }
+        indices
+    }
+
+    /// The line of `key` inside the `[table]` section of the main file.
+    fn key_line(&self, table: &str, key: &str) -> Option<usize> {
+        self.key_lines.get(table)?.get(key).copied()
+    }
+}
+
+/// The lines of the keys of every table in `source`, from the parser's spans.
+fn key_lines(source: &str) -> HashMap<String, HashMap<String, usize>> {
+    let sections: HashMap<String, Section> = toml::from_str(source).unwrap_or_default();
+    sections
+        .into_iter()
+        .map(|(name, section)| {
+            let keys = section
+                .0
+                .into_iter()
+                .map(|key| {
+                    let line = line_of_offset(source, key.span().start);
+                    (key.into_inner(), line)
+                })
+                .collect();
+            (name, keys)
+        })
+        .collect()
+}
+
+/// The keys of a top-level table, values that aren't tables have none.
+#[derive(Default)]
+struct Section(Vec<Spanned<String>>);
+
+impl<'de> Deserialize<'de> for Section {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(SectionVisitor)
+    }
+}
+
+struct SectionVisitor;
+
+impl<'de> Visitor<'de> for SectionVisitor {
+    type Value = Section;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "any value")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Section, A::Error> {
+        let mut keys = vec![];
+        while let Some(key) = map.next_key::<Spanned<String>>()? {
+            map.next_value::<IgnoredAny>()?;
+            keys.push(key);
+        }
+        Ok(Section(keys))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Section, A::Error> {
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(Section::default())
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Section, E> {
+        Ok(Section::default())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<Section, E> {
+        Ok(Section::default())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Section, E> {
+        Ok(Section::default())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<Section, E> {
+        Ok(Section::default())
+    }
+}
+
+fn channel_table(channel_type: &ChannelType) -> &'static str {
+    match channel_type {
+        ChannelType::Chan => "channels",
+        ChannelType::Aux => "aux_channels",
+        ChannelType::Group => "monitor_groups",
+    }
+}
+
+fn line_of_offset(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"
+[network]
+ip_address = "127.0.0.1:8000"
+
+[channels]
+2 = "OT"
+
+[aux_channels]
+4 = "H90"
+
+[monitor_groups]
+
+[midi_mapping_cc] # faders
+1 = "vol(99)"
+2 = "send(2,7)"
+3 = "vol(ot)"
+300 = "vol(2)"
+"44" = "vol(2)"
+5 = "volume"
+
+[midi_mapping_note_on]
+[midi_mapping_note_off]
+"#;
+
+    #[test]
+    fn test_check_reports_lines() {
+        let diagnostics = check(SOURCE);
+        let lines: Vec<Option<usize>> = diagnostics.iter().map(|d| d.line).collect();
+        assert_eq!(
+            lines,
+            vec![Some(14), Some(15), Some(17), Some(18), Some(19)],
+            "{:#?}",
+            diagnostics
+        );
+        assert!(diagnostics[0].message.contains("chan(99)"));
+        assert!(diagnostics[1].message.contains("aux(7)"));
+    }
+
+    #[test]
+    fn test_check_macros_and_osc_mapping() {
+        let source = SOURCE
+            .lines()
+            .take(12)
+            .chain([
+                "[macros]",
+                "check = [\"mute(2)\", \"vol(Bass)=0dB\"]",
+                "\"line check\" = [\"send(2,9)=0dB\"]",
+                "[osc_mapping]",
+                "fader1 = { command = \"vol(OT)\" }",
+                "\"fader 2\" = \"vol(5)\"",
+            ])
+            .collect::<Vec<_>>()
+            .join("\n");
+        let diagnostics = check(&source);
+        let reported: Vec<(Option<usize>, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.line, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            reported,
+            vec![
+                (Some(14), "[macros] check: Unknown chan name: Bass"),
+                (
+                    Some(15),
+                    "[macros] line check: aux(9) is not defined in [aux_channels]"
+                ),
+                (
+                    Some(18),
+                    "[osc_mapping] fader 2: chan(5) is not defined in [channels]"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_valid_config() {
+        let source = SOURCE
+            .lines()
+            .filter(|line| !line.contains("99") && !line.contains(",7") && !line.contains("300"))
+            .filter(|line| !line.contains("volume"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(check(&source), vec![]);
+    }
+}
@@ -0,0 +1,102 @@
+use std::error::Error;
+use std::fs;
+
+use toml_edit::{value, Document, Item, Table};
+
+/// A config file opened for editing, comments and key order survive a save.
+pub struct ConfigDocument {
+    file_name: String,
+    document: Document,
+}
+
+impl ConfigDocument {
+    pub fn open(file_name: &str) -> Result<ConfigDocument, Box<dyn Error>> {
+        let document = fs::read_to_string(file_name)?.parse::<Document>()?;
+        Ok(ConfigDocument {
+            file_name: file_name.to_string(),
+            document,
+        })
+    }
+
+    /// Returns the command currently mapped to `key` in `table`.
+    pub fn mapping(&self, table: &str, key: usize) -> Option<String> {
+        let item = self.document.get(table)?.get(key.to_string())?;
+        match item.as_str() {
+            Some(command) => Some(command.to_string()),
+            None => item.get("command")?.as_str().map(String::from),
+        }
+    }
+
+    /// Maps `key` to `command`, keeping any options of an existing table entry.
+    /// Returns the command that was replaced.
+    pub fn set_mapping(&mut self, table: &str, key: usize, command: &str) -> Option<String> {
+        let previous = self.mapping(table, key);
+        let mapping_table = self
+            .document
+            .as_table_mut()
+            .entry(table)
+            .or_insert(Item::Table(Table::new()));
+        let entry = &mut mapping_table[key.to_string().as_str()];
+        match entry.as_inline_table_mut() {
+            Some(options) => {
+                options.insert("command", command.into());
+            }
+            None => *entry = value(command),
+        }
+        previous
+    }
+
+    /// Sets an option of the mapping at `key`, turning a plain command into a table.
+    pub fn set_option(&mut self, table: &str, key: usize, name: &str, option: &str) {
+        let Some(entry) = self
+            .document
+            .get_mut(table)
+            .and_then(|table| table.get_mut(key.to_string().as_str()))
+        else {
+            return;
+        };
+        if let Some(command) = entry.as_str() {
+            let mut options = toml_edit::InlineTable::new();
+            options.insert("command", command.into());
+            *entry = value(options);
+        }
+        if let Some(options) = entry.as_inline_table_mut() {
+            options.insert(name, option.into());
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        fs::write(&self.file_name, self.document.to_string())?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for ConfigDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_mapping_keeps_comments_and_options() {
+        let source = "[midi_mapping_cc]\n# faders\n1 = \"vol(2)\"\n2 = { command = \"vol(4)\", curve = \"linear\" }\n";
+        let mut document = ConfigDocument {
+            file_name: String::new(),
+            document: source.parse().unwrap(),
+        };
+        assert_eq!(
+            document.set_mapping("midi_mapping_cc", 2, "vol(6)"),
+            Some(String::from("vol(4)"))
+        );
+        assert_eq!(document.set_mapping("midi_mapping_cc", 3, "vol(8)"), None);
+        document.set_option("midi_mapping_cc", 3, "curve", "linear");
+        assert_eq!(
+            document.to_string(),
+            "[midi_mapping_cc]\n# faders\n1 = \"vol(2)\"\n2 = { command = \"vol(6)\", curve = \"linear\" }\n3 = { command = \"vol(8)\", curve = \"linear\" }\n"
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+/// A config file with its `include`s and the selected profile merged in.
+pub struct LoadedConfig {
+    pub table: Table,
+    /// Every file that went into the config, the main file first.
+    pub files: Vec<PathBuf>,
+}
+
+/// Loads `file_name`, merging its includes and the selected profile.
+///
+/// `include = ["base.toml"]` loads other files (relative to the including file)
+/// underneath this one. `[profiles.<name>]` tables are laid over the result when
+/// that profile is selected, and can build on each other with `inherits = "<name>"`.
+pub fn load(file_name: &str, profile: Option<&str>) -> Result<LoadedConfig, Box<dyn Error>> {
+    let mut files = vec![];
+    let mut table = load_file(Path::new(file_name), &mut vec![], &mut files)?;
+
+    let profiles = match table.remove("profiles") {
+        Some(Value::Table(profiles)) => profiles,
+        Some(_) => return Err("profiles must be a table of named profiles".into()),
+        None => Table::new(),
+    };
+    if let Some(profile) = profile {
+        apply_profile(&mut table, &profiles, profile, &mut vec![])?;
+    }
+    Ok(LoadedConfig { table, files })
+}
+
+fn load_file(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<Table, Box<dyn Error>> {
+    if stack.iter().any(|file| file == path) {
+        return Err(format!("{} includes itself", path.display()).into());
+    }
+    stack.push(path.to_path_buf());
+    if !files.iter().any(|file| file == path) {
+        files.push(path.to_path_buf());
+    }
+    let source = fs::read_to_string(path)
+        .map_err(|err| format!("Error reading {}: {}", path.display(), err))?;
+    let mut table: Table = toml::from_str(&source)
+        .map_err(|err| format!("Error parsing {}: {}", path.display(), err))?;
+
+    let includes = match table.remove("include") {
+        Some(Value::Array(includes)) => includes,
+        Some(Value::String(include)) => vec![Value::String(include)],
+        Some(_) => {
+            return Err(format!("include in {} must be a list of files", path.display()).into())
+        }
+        None => vec![],
+    };
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let mut merged = Table::new();
+    for include in includes {
+        let include = include
+            .as_str()
+            .ok_or_else(|| format!("include in {} must be a list of files", path.display()))?;
+        merge(
+            &mut merged,
+            load_file(&directory.join(include), stack, files)?,
+        );
+    }
+    merge(&mut merged, table);
+    stack.pop();
+    Ok(merged)
+}
+
+fn apply_profile(
+    table: &mut Table,
+    profiles: &Table,
+    profile: &str,
+    chain: &mut Vec<String>,
+) -> Result<(), String> {
+    if chain.iter().any(|name| name == profile) {
+        return Err(format!("Profile {} inherits from itself", profile));
+    }
+    chain.push(profile.to_string());
+    let mut overlay = match profiles.get(profile) {
+        Some(Value::Table(overlay)) => overlay.clone(),
+        _ => {
+            let available: Vec<&String> = profiles.keys().collect();
+            return Err(format!(
+                "Unknown profile {}, available profiles: {:?}",
+                profile, available
+            ));
+        }
+    };
+    if let Some(parent) = overlay.remove("inherits") {
+        let parent = parent
+            .as_str()
+            .ok_or_else(|| format!("inherits in profile {} must be a profile name", profile))?;
+        apply_profile(table, profiles, parent, chain)?;
+    }
+    merge(table, overlay);
+    Ok(())
+}
+
+/// Lays `overlay` over `base`, tables are merged key by key and everything else is replaced.
+pub fn merge(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(overlay_table)) => {
+                merge(base_table, overlay_table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_inherit() {
+        let mut table: Table = toml::from_str(
+            r#"
+            [channels]
+            0 = "H90"
+            2 = "OT"
+
+            [profiles.live.channels]
+            2 = "Drums"
+
+            [profiles.festival]
+            inherits = "live"
+            channels = { 4 = "Keys" }
+            "#,
+        )
+        .unwrap();
+        let profiles = table.remove("profiles").unwrap();
+        apply_profile(
+            &mut table,
+            profiles.as_table().unwrap(),
+            "festival",
+            &mut vec![],
+        )
+        .unwrap();
+        let channels = table["channels"].as_table().unwrap();
+        assert_eq!(channels["0"].as_str(), Some("H90"));
+        assert_eq!(channels["2"].as_str(), Some("Drums"));
+        assert_eq!(channels["4"].as_str(), Some("Keys"));
+
+        assert!(apply_profile(
+            &mut table,
+            profiles.as_table().unwrap(),
+            "studio",
+            &mut vec![]
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::stdin;
+use std::thread;
+use std::time::Duration;
+
+use crate::motu::channel::ChannelNames;
+use crate::motu::{Motu, MotuCommand};
+
+/// A single line of a cue file.
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    Commands(Vec<MotuCommand>),
+    Wait(Duration),
+    WaitForKey,
+    Label(String),
+}
+
+/// A cue file: DSL commands run in order, with waits in between.
+///
+/// ```text
+/// # song 1
+/// intro:
+/// monitor(on); vol(OT)=0dB
+/// wait 2s
+/// send(OT,Space)=-12dB
+/// wait for key
+/// ```
+///
+/// Lines ending in `:` are labels, playback can start at one. `#` starts a comment.
+/// Faders, sends and pans need a value and macros have to be defined in the config,
+/// the whole file is checked before it plays.
+#[derive(Debug, PartialEq)]
+pub struct Cue {
+    steps: Vec<Step>,
+}
+
+impl Cue {
+    pub fn read(
+        file_name: &str,
+        names: &ChannelNames,
+        macros: &BTreeMap<String, Vec<MotuCommand>>,
+    ) -> Result<Cue, Box<dyn Error>> {
+        Ok(Cue::parse(&fs::read_to_string(file_name)?, names, macros)?)
+    }
+
+    /// Parses a cue file, errors name the line they were found on.
+    pub fn parse(
+        source: &str,
+        names: &ChannelNames,
+        macros: &BTreeMap<String, Vec<MotuCommand>>,
+    ) -> Result<Cue, String> {
+        let mut steps = vec![];
+        for (index, line) in source.lines().enumerate() {
+            let line = match line.split_once('#') {
+                Some((line, _comment)) => line.trim(),
+                None => line.trim(),
+            };
+            if line.is_empty() {
+                continue;
+            }
+            let step = parse_step(line, names, macros)
+                .map_err(|err| format!("line {}: {}", index + 1, err))?;
+            if let Step::Label(label) = &step {
+                if steps.contains(&step) {
+                    return Err(format!("line {}: label {} is used twice", index + 1, label));
+                }
+            }
+            steps.push(step);
+        }
+        Ok(Cue { steps })
+    }
+
+    /// Runs the cue on `motu`, starting at the label `from` if given.
+    pub fn play(&self, motu: &Motu, from: Option<&str>) -> Result<(), Box<dyn Error>> {
+        for step in self.steps_from(from)? {
+            match step {
+                Step::Label(label) => println!("{}:", label),
+                Step::Commands(commands) => {
+                    println!(
+                        "{}",
+                        commands
+                            .iter()
+                            .map(MotuCommand::to_string)
+                            .collect::<Vec<String>>()
+                            .join("; ")
+                    );
+                    motu.run(commands)?;
+                }
+                Step::Wait(duration) => thread::sleep(*duration),
+                Step::WaitForKey => {
+                    println!("Press enter to continue...");
+                    stdin().read_line(&mut String::new())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn steps_from(&self, from: Option<&str>) -> Result<&[Step], String> {
+        let Some(from) = from else {
+            return Ok(&self.steps);
+        };
+        self.steps
+            .iter()
+            .position(|step| matches!(step, Step::Label(label) if label == from))
+            .map(|index| &self.steps[index..])
+            .ok_or_else(|| format!("No label {} in the cue", from))
+    }
+}
+
+fn parse_step(
+    line: &str,
+    names: &ChannelNames,
+    macros: &BTreeMap<String, Vec<MotuCommand>>,
+) -> Result<Step, String> {
+    if let Some(label) = line.strip_suffix(':') {
+        return Ok(Step::Label(label.trim().to_string()));
+    }
+    match line.strip_prefix("wait ").map(str::trim) {
+        Some("for key") => Ok(Step::WaitForKey),
+        Some(duration) => parse_duration(duration).map(Step::Wait),
+        None => {
+            let commands = MotuCommand::parse_list(line, names)?;
+            for command in &commands {
+                if let MotuCommand::Macro(name) = command {
+                    if !macros.contains_key(name) {
+                        return Err(format!("Unknown macro {}", name));
+                    }
+                }
+            }
+            Ok(Step::Commands(commands))
+        }
+    }
+}
+
+/// Parses `2s`, `1.5s` or `500ms`.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid wait {}, expected e.g. 2s or 500ms", s);
+    let (number, unit) = match s.strip_suffix("ms") {
+        Some(number) => (number, 0.001),
+        None => (s.strip_suffix('s').ok_or_else(invalid)?, 1.0),
+    };
+    let seconds = number.trim().parse::<f64>().map_err(|_| invalid())? * unit;
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cue() {
+        let cue = Cue::parse(
+            "# soundcheck\nintro:\nmonitor(on); vol(2)=0dB\n\nwait 1.5s\nwait 250ms # short\nwait for key\n",
+            &ChannelNames::default(),
+            &BTreeMap::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            cue.steps,
+            vec![
+                Step::Label(String::from("intro")),
+                Step::Commands(vec![
+                    MotuCommand::EnableMonitoring,
+                    MotuCommand::parse_assignment("vol(2)=0dB", &ChannelNames::default()).unwrap(),
+                ]),
+                Step::Wait(Duration::from_millis(1500)),
+                Step::Wait(Duration::from_millis(250)),
+                Step::WaitForKey,
+            ]
+        );
+        assert_eq!(cue.steps_from(Some("intro")).unwrap().len(), 5);
+        assert!(cue.steps_from(Some("outro")).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_line() {
+        let names = ChannelNames::default();
+        let macros = BTreeMap::from([(String::from("vocal_check"), vec![])]);
+        let parse = |source| Cue::parse(source, &names, &macros);
+        assert_eq!(
+            parse("vol(2)=0dB\nwait 2 minutes\n"),
+            Err(String::from(
+                "line 2: Invalid wait 2 minutes, expected e.g. 2s or 500ms"
+            ))
+        );
+        assert!(parse("vol(2)=loud").is_err());
+        // a missing value would send a placeholder level in the middle of the show
+        assert_eq!(
+            parse("intro:\nmute(3)\nwait 1s\nsend(2,4)\n"),
+            Err(String::from(
+                "line 4: send(2,4) needs a value, e.g. send(2,4)=-6dB"
+            ))
+        );
+        assert!(parse("a:\nb:\na:").is_err());
+        // a typo in a macro name is found before mute(2) goes out
+        assert_eq!(
+            parse("mute(2)\nmacro(vocal_check)\nmacro(vocal_chek)\n"),
+            Err(String::from("line 3: Unknown macro vocal_chek"))
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::motu::channel::{Channel, ChannelType};
+use crate::motu::MotuCommand;
+
+/// Builds an Open Stage Control session with one strip per configured channel: a
+/// fader, a mute button and a send knob for every aux, bound to the interface's OSC
+/// addresses and labelled with the config names.
+pub fn open_stage_control_session(config: &Config) -> Value {
+    let strips: Vec<Value> = config
+        .channels
+        .iter()
+        .map(|(index, name)| strip(config, Channel::new(*index as i32, ChannelType::Chan), name))
+        .collect();
+    json!({
+        "createdWith": "Open Stage Control",
+        "version": "1.25.0",
+        "type": "session",
+        "content": {
+            "type": "root",
+            "id": "root",
+            "layout": "horizontal",
+            "widgets": strips,
+            "tabs": [],
+        },
+    })
+}
+
+fn strip(config: &Config, channel: Channel, name: &str) -> Value {
+    let id = format!("chan_{}", channel.channel_number());
+    let sends: Vec<Value> = config
+        .aux_channels
+        .iter()
+        .map(|(aux_index, aux_name)| {
+            let send = MotuCommand::Send {
+                channel,
+                aux_channel: Channel::new(*aux_index as i32, ChannelType::Aux),
+                value: 0.0,
+            };
+            json!({
+                "type": "knob",
+                "id": format!("{}_send_{}", id, aux_index),
+                "label": aux_name,
+                "address": address(&send),
+                "range": { "min": 0, "max": 1 },
+            })
+        })
+        .collect();
+    let fader = MotuCommand::Volume {
+        channel,
+        volume: 0.0,
+    };
+    json!({
+        "type": "panel",
+        "id": id,
+        "label": name,
+        "layout": "vertical",
+        "widgets": [
+            {
+                "type": "panel",
+                "id": format!("{}_sends", id),
+                "layout": "vertical",
+                "widgets": sends,
+            },
+            {
+                "type": "fader",
+                "id": format!("{}_fader", id),
+                "label": name,
+                "address": address(&fader),
+                "range": { "min": 0, "max": 1 },
+                "expand": true,
+            },
+            {
+                "type": "button",
+                "id": format!("{}_mute", id),
+                "label": "mute",
+                "mode": "toggle",
+                "address": address(&MotuCommand::Mute(channel)),
+                "on": 1,
+                "off": 0,
+            },
+        ],
+    })
+}
+
+fn address(command: &MotuCommand) -> String {
+    command
+        .osc_command()
+        .map(|(address, _)| address)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_per_channel() {
+        let config = Config::parse(
+            r#"
+            [network]
+            ip_address = "127.0.0.1:8000"
+            [channels]
+            2 = "OT"
+            10 = "Syntakt"
+            [aux_channels]
+            4 = "H90"
+            [monitor_groups]
+            [midi_mapping_cc]
+            [midi_mapping_note_on]
+            [midi_mapping_note_off]
+            "#,
+        )
+        .unwrap();
+        let session = open_stage_control_session(&config);
+        let strips = session["content"]["widgets"].as_array().unwrap();
+        assert_eq!(strips.len(), 2);
+        assert_eq!(strips[0]["label"], "OT");
+        let widgets = &strips[1]["widgets"];
+        assert_eq!(
+            widgets[0]["widgets"][0]["address"],
+            "/mix/chan/10/matrix/aux/4/send"
+        );
+        assert_eq!(widgets[0]["widgets"][0]["label"], "H90");
+        assert_eq!(widgets[1]["address"], "/mix/chan/10/matrix/fader");
+        assert_eq!(widgets[2]["address"], "/mix/chan/10/matrix/mute");
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::config::edit::ConfigDocument;
+use crate::midi::notemode::NoteMode;
+use crate::motu::channel::ChannelNames;
+use crate::motu::MotuCommand;
+
+/// An oscConfig JSON file, as written by the original python bridge.
+#[derive(Debug, Deserialize)]
+struct TopLevel {
+    #[serde(rename = "oscConfig")]
+    osc_config: OscConfig,
+}
+
+#[derive(Debug, Deserialize)]
+struct OscConfig {
+    #[serde(default)]
+    control_change: BTreeMap<usize, Entry>,
+    #[serde(default)]
+    note_on: BTreeMap<usize, Entry>,
+    #[serde(default)]
+    note_off: BTreeMap<usize, Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Entry {
+    address: Option<String>,
+    command: Option<String>,
+}
+
+/// A mapping converted from an oscConfig entry.
+#[derive(Debug, PartialEq)]
+pub struct ImportedMapping {
+    pub table: &'static str,
+    pub key: usize,
+    pub command: MotuCommand,
+    /// Switches on a CC, e.g. a mute, toggle with each press of the button.
+    pub mode: NoteMode,
+}
+
+/// The result of reading an oscConfig file.
+#[derive(Debug, Default)]
+pub struct Import {
+    pub mappings: Vec<ImportedMapping>,
+    /// Entries that couldn't be converted, with the reason.
+    pub skipped: Vec<String>,
+}
+
+impl Import {
+    /// Reads the oscConfig file at `file_name`. `command` fields are parsed as the
+    /// mapping DSL, channel names in them are resolved through `names`.
+    pub fn read(file_name: &str, names: &ChannelNames) -> Result<Import, Box<dyn Error>> {
+        Import::parse(&fs::read_to_string(file_name)?, names)
+    }
+
+    pub fn parse(source: &str, names: &ChannelNames) -> Result<Import, Box<dyn Error>> {
+        let osc_config = serde_json::from_str::<TopLevel>(source)?.osc_config;
+        let mut import = Import::default();
+        for (table, entries) in [
+            ("midi_mapping_cc", osc_config.control_change),
+            ("midi_mapping_note_on", osc_config.note_on),
+            ("midi_mapping_note_off", osc_config.note_off),
+        ] {
+            for (key, entry) in entries {
+                let command = match (&entry.address, &entry.command) {
+                    (Some(address), _) => MotuCommand::from_osc_address(address),
+                    (None, Some(command)) => MotuCommand::parse(command, names),
+                    (None, None) => Err("Neither address nor command given".to_string()),
+                };
+                match command {
+                    Ok(command) => {
+                        let switch = !command.has_value() && command.inverse().is_some();
+                        let mode = if table == "midi_mapping_cc" && switch {
+                            NoteMode::Toggle
+                        } else {
+                            NoteMode::default()
+                        };
+                        import.mappings.push(ImportedMapping {
+                            table,
+                            key,
+                            command,
+                            mode,
+                        })
+                    }
+                    Err(err) => import.skipped.push(format!("[{}] {}: {}", table, key, err)),
+                }
+            }
+        }
+        Ok(import)
+    }
+
+    /// Writes the mappings into a config, returning the ones that replaced a
+    /// different existing mapping.
+    pub fn merge_into(&self, document: &mut ConfigDocument) -> Vec<String> {
+        let mut replaced = vec![];
+        for mapping in &self.mappings {
+            let command = mapping.command.to_string();
+            if let Some(previous) = document.set_mapping(mapping.table, mapping.key, &command) {
+                if previous != command {
+                    replaced.push(format!(
+                        "[{}] {}: {} replaced by {}",
+                        mapping.table, mapping.key, previous, command
+                    ));
+                }
+            }
+            if mapping.mode == NoteMode::Toggle {
+                document.set_option(mapping.table, mapping.key, "mode", "toggle");
+            }
+        }
+        replaced
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_osc_config() {
+        let source = r#"{ "oscConfig": {
+            "control_change": {
+                "1": { "type": "fader", "address": "/mix/chan/42/matrix/fader" },
+                "2": { "type": "fader", "address": "/mix/chan/42/matrix/aux/0/send" },
+                "3": { "type": "knob", "address": "/mix/chan/42/matrix/pan" },
+                "4": { "type": "knob", "address": "/mix/chan/42/matrix/solo" },
+                "5": { "type": "button", "address": "/mix/chan/42/matrix/mute" }
+            },
+            "note_on": {
+                "60": { "address": "/mix/group/1/matrix/mute" },
+                "61": { "command": "monitor(on)" },
+                "62": {}
+            }
+        } }"#;
+        let import = Import::parse(source, &ChannelNames::default()).unwrap();
+        let commands: Vec<(&str, usize, String)> = import
+            .mappings
+            .iter()
+            .map(|mapping| (mapping.table, mapping.key, mapping.command.to_string()))
+            .collect();
+        assert_eq!(
+            commands,
+            vec![
+                ("midi_mapping_cc", 1, String::from("vol(42)")),
+                ("midi_mapping_cc", 2, String::from("send(42,0)")),
+                ("midi_mapping_cc", 3, String::from("pan(42)")),
+                ("midi_mapping_cc", 5, String::from("mute(42)")),
+                ("midi_mapping_note_on", 60, String::from("mute(group:1)")),
+                ("midi_mapping_note_on", 61, String::from("monitor(on)")),
+            ]
+        );
+        assert_eq!(import.skipped.len(), 2);
+        assert!(import.skipped[0].contains("/mix/chan/42/matrix/solo"));
+        let modes: Vec<NoteMode> = import.mappings.iter().map(|m| m.mode).collect();
+        assert_eq!(modes[3], NoteMode::Toggle);
+        assert_eq!(modes[4], NoteMode::Trigger);
+    }
+}
@@ -1,31 +1,47 @@
 pub mod args;
 pub mod args_test;
 pub mod config;
+pub mod cue;
+pub mod export;
+pub mod import;
+pub mod meters;
 pub mod midi;
 pub mod motu;
+pub mod osc_bridge;
+pub mod shell;
 
 #[cfg(test)]
 mod tests {
     use crate::{
         args::IpEndpoint,
         config::{Config, NetworkConfig},
-        motu::{channel::Channel, channel::ChannelType, Motu, MotuCommand},
+        motu::{
+            channel::Channel,
+            channel::ChannelType,
+            transport::{RecordingTransport, TransportPolicy},
+            Motu, MotuCommand,
+        },
     };
-    use std::{collections::HashMap, error::Error};
+    use std::{collections::BTreeMap, error::Error};
 
     fn get_mock_config() -> Config {
         let mut mock_config = Config {
             network: NetworkConfig {
                 ip_address: IpEndpoint::from("127.0.0.1:8000"),
+                transport: TransportPolicy::default(),
             },
             ip_address: IpEndpoint::from("127.0.0.1:8000"),
-            aux_channels: HashMap::new(),
-            channels: HashMap::new(),
-            monitor_groups: HashMap::new(),
+            aux_channels: BTreeMap::new(),
+            channels: BTreeMap::new(),
+            monitor_groups: BTreeMap::new(),
             midi_config: None,
-            midi_mapping_cc: HashMap::new(),
-            midi_mapping_note_on: HashMap::new(),
-            midi_mapping_note_off: HashMap::new(),
+            midi_mapping_cc: BTreeMap::new(),
+            midi_mapping_note_on: BTreeMap::new(),
+            midi_mapping_note_off: BTreeMap::new(),
+            osc_server: None,
+            osc_mapping: BTreeMap::new(),
+            macros: BTreeMap::new(),
+            files: vec![],
         };
         mock_config.aux_channels.insert(1, String::from("Aux 1"));
         mock_config.channels.insert(1, String::from("Channel 1"));
@@ -89,4 +105,81 @@ mod tests {
         // Add assertions here to check that send is set correctly
         Ok(())
     }
+
+    #[test]
+    fn test_transport_policy() -> Result<(), Box<dyn Error>> {
+        let mut mock_config = get_mock_config();
+        for index in 2..12 {
+            mock_config
+                .channels
+                .insert(index, format!("Channel {}", index));
+        }
+        let osc = RecordingTransport::default();
+        let http = RecordingTransport::default();
+        let motu =
+            Motu::with_transports(Box::new(osc.clone()), Box::new(http.clone()), &mock_config)?;
+        motu.run(&[MotuCommand::EnableMonitoring])?;
+        // 11 faders, 11 sends and a monitor group
+        motu.run(&[MotuCommand::Init])?;
+        assert_eq!(
+            osc.sent(),
+            vec![vec![MotuCommand::Unmute(Channel::new(
+                1,
+                ChannelType::Group
+            ))]]
+        );
+        assert_eq!(http.sent().len(), 1);
+        assert_eq!(http.sent()[0].len(), 23);
+
+        mock_config.network.transport = TransportPolicy::Osc;
+        let motu =
+            Motu::with_transports(Box::new(osc.clone()), Box::new(http.clone()), &mock_config)?;
+        motu.run(&[MotuCommand::Init])?;
+        assert_eq!(osc.sent().len(), 2);
+        assert_eq!(http.sent().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_init() -> Result<(), Box<dyn Error>> {
+        let mut mock_config = get_mock_config();
+        mock_config.network.transport = TransportPolicy::Osc;
+        let osc = RecordingTransport::default();
+        let motu = Motu::with_transports(
+            Box::new(osc.clone()),
+            Box::new(RecordingTransport::default()),
+            &mock_config,
+        )?;
+        let vol = |volume| MotuCommand::Volume {
+            channel: Channel::new(1, ChannelType::Chan),
+            volume,
+        };
+        motu.run(&[vol(0.5)])?;
+        motu.run(&[MotuCommand::Init, MotuCommand::Undo])?;
+        assert_eq!(osc.sent()[2], vec![vol(0.5)]);
+        motu.run(&[MotuCommand::Redo])?;
+        assert_eq!(osc.sent()[3], vec![vol(1.0)]);
+        // nothing left to redo
+        motu.run(&[MotuCommand::Redo])?;
+        assert_eq!(osc.sent().len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_macro() -> Result<(), Box<dyn Error>> {
+        let osc = RecordingTransport::default();
+        let motu = Motu::with_transports(
+            Box::new(osc.clone()),
+            Box::new(RecordingTransport::default()),
+            &get_mock_config(),
+        )?;
+        let result = motu.run(&[
+            MotuCommand::EnableMonitoring,
+            MotuCommand::Macro(String::from("typo")),
+        ]);
+        assert_eq!(result.unwrap_err().to_string(), "Unknown macro typo");
+        // nothing of the batch is sent
+        assert!(osc.sent().is_empty());
+        Ok(())
+    }
 }
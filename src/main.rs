@@ -1,6 +1,18 @@
-use motuman::{args::Args, config, motu};
+use motuman::{
+    args::{Args, Command, ConfigAction},
+    config::{self, edit::ConfigDocument},
+    cue::Cue,
+    export,
+    import::Import,
+    meters,
+    midi::listen,
+    motu::{self, journal::Journal},
+    shell,
+};
 use std::env;
+use std::fs;
 use std::process;
+use std::sync::{Arc, Mutex};
 
 use std::net::IpAddr;
 
@@ -11,35 +23,105 @@ pub struct IpEndpoint {
 }
 
 fn main() {
-    // Get the current working directory and print it to the console
+    // Get the current working directory and print it to the console, on stderr so
+    // output meant for files, e.g. `export` or `import --print`, stays clean
     if let Ok(cwd) = env::current_dir() {
-        println!("Current working directory: {:?}", cwd);
+        eprintln!("Current working directory: {:?}", cwd);
     } else {
         eprintln!("Error getting current working directory");
     }
 
     // Initialize the command line arguments
     let args = Args::init();
-    println!("Args: {:?}", args);
+    eprintln!("Args: {:?}", args);
 
     // Get the configuration file name, IP address, and MOTU commands from the command line arguments
     let config_file_name = args.config_file_name();
-    let ip_address = args.ip_address;
-    let motu_commands = args.motu_commands();
+
+    match &args.command {
+        Command::Config {
+            action: ConfigAction::Check,
+        } => {
+            check_config(&config_file_name, args.profile.as_deref());
+            return;
+        }
+        Command::Import { path, print } => {
+            import(path, &config_file_name, *print);
+            return;
+        }
+        Command::Listen { learn } => {
+            let result = if *learn {
+                listen::learn(&config_file_name, args.profile.as_deref())
+            } else {
+                listen::listen(
+                    &config_file_name,
+                    args.profile.as_deref(),
+                    args.network(),
+                    args.dry_run,
+                )
+            };
+            if let Err(err) = result {
+                eprintln!("Error: {err}");
+                process::exit(1);
+            }
+            return;
+        }
+        _ => (),
+    }
 
     // Build the configuration object
-    let config = config::Config::build(config_file_name, ip_address).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {err}");
-        process::exit(1);
-    });
+    let config = config::Config::build(config_file_name, args.network(), args.profile.as_deref())
+        .unwrap_or_else(|err| {
+            eprintln!("Problem parsing arguments: {err}");
+            process::exit(1);
+        });
+
+    if let Command::Export { output } = &args.command {
+        export_session(&config, output.as_deref());
+        return;
+    }
+    if let Command::Meters = &args.command {
+        if let Err(err) = meters::meters(&config) {
+            eprintln!("Error reading meters: {err}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Channel names on the command line are resolved through the config
+    let motu_commands = args
+        .motu_commands(&config.channel_names())
+        .unwrap_or_else(|err| {
+            eprintln!("Problem parsing arguments: {err}");
+            process::exit(1);
+        });
 
     let ip: &str = &config.ip_address.address.to_string();
     let port = &config.ip_address.port.to_string();
 
     // Create a new MOTU object and run the specified commands
-    match motu::Motu::new(ip, port, &config) {
+    let motu = if args.dry_run {
+        motu::Motu::dry_run(ip, port, &config)
+    } else {
+        motu::Motu::new(ip, port, &config)
+    };
+    // a dry run reads the journal, so undo shows what it would do, but doesn't change it
+    let motu = motu.and_then(|mut motu| {
+        motu.set_journal(Arc::new(Mutex::new(Journal::open_default(args.dry_run)?)));
+        Ok(motu)
+    });
+    match motu {
         Ok(motu) => {
-            if let Err(e) = motu.run(&motu_commands) {
+            let result = match &args.command {
+                Command::Shell => shell::shell(&motu, &config.channel_names()),
+                Command::Play { path, from } => config
+                    .macros()
+                    .map_err(Into::into)
+                    .and_then(|macros| Cue::read(path, &config.channel_names(), &macros))
+                    .and_then(|cue| cue.play(&motu, from.as_deref())),
+                _ => motu.run(&motu_commands),
+            };
+            if let Err(e) = result {
                 eprintln!("Application error: {e}");
                 process::exit(1);
             }
@@ -50,3 +132,88 @@ fn main() {
         }
     }
 }
+
+/// Prints every problem found in the config file and exits with an error if there are any.
+fn check_config(config_file_name: &str, profile: Option<&str>) {
+    let source = fs::read_to_string(config_file_name).unwrap_or_else(|err| {
+        eprintln!("Error reading {config_file_name}: {err}");
+        process::exit(1);
+    });
+    // includes and the profile are merged in, line numbers refer to the main file
+    let diagnostics = match config::profile::load(config_file_name, profile) {
+        Ok(loaded) => config::check::check_table(&source, loaded.table),
+        // e.g. a missing include or profile, the main file is still checked on its own
+        Err(err) => {
+            let mut diagnostics = vec![config::check::Diagnostic {
+                line: None,
+                message: err.to_string(),
+            }];
+            diagnostics.extend(config::check::check(&source));
+            diagnostics
+        }
+    };
+    for diagnostic in &diagnostics {
+        match diagnostic.line {
+            Some(line) => println!("{}:{}: {}", config_file_name, line, diagnostic.message),
+            None => println!("{}: {}", config_file_name, diagnostic.message),
+        }
+    }
+    if diagnostics.is_empty() {
+        println!("{config_file_name}: ok");
+    } else {
+        println!("{} problem(s) found", diagnostics.len());
+        process::exit(1);
+    }
+}
+
+/// Merges the mappings of an oscConfig file into the config file, reporting what
+/// couldn't be converted and which mappings were replaced.
+fn import(path: &str, config_file_name: &str, print: bool) {
+    // names are only needed for `command` fields, a config that doesn't load yet is fine
+    let names = config::Config::build(config_file_name.to_string(), Default::default(), None)
+        .map(|config| config.channel_names())
+        .unwrap_or_default();
+    let import = Import::read(path, &names).unwrap_or_else(|err| {
+        eprintln!("Error reading {path}: {err}");
+        process::exit(1);
+    });
+    let mut document = ConfigDocument::open(config_file_name).unwrap_or_else(|err| {
+        eprintln!("Error reading {config_file_name}: {err}");
+        process::exit(1);
+    });
+    for replaced in import.merge_into(&mut document) {
+        eprintln!("{replaced}");
+    }
+    for skipped in &import.skipped {
+        eprintln!("Skipped {skipped}");
+    }
+    if print {
+        print!("{document}");
+    } else if let Err(err) = document.save() {
+        eprintln!("Error writing {config_file_name}: {err}");
+        process::exit(1);
+    } else {
+        println!(
+            "Imported {} mapping(s) into {}, skipped {}",
+            import.mappings.len(),
+            config_file_name,
+            import.skipped.len()
+        );
+    }
+}
+
+/// Writes the Open Stage Control session for the config to `output`, or stdout.
+fn export_session(config: &config::Config, output: Option<&str>) {
+    let session = export::open_stage_control_session(config);
+    let session = serde_json::to_string_pretty(&session).expect("session is valid JSON");
+    match output {
+        Some(output) => {
+            if let Err(err) = fs::write(output, session) {
+                eprintln!("Error writing {output}: {err}");
+                process::exit(1);
+            }
+            println!("Wrote {} strip(s) to {output}", config.channels.len());
+        }
+        None => println!("{session}"),
+    }
+}
@@ -0,0 +1,330 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{stdout, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use reqwest::blocking::Client;
+use serde::de::{Deserialize, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde_json::Value;
+
+use crate::config::Config;
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+const PEAK_HOLD: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+/// Lowest level shown, quieter ones leave the bar empty.
+const FLOOR_DB: f32 = -60.0;
+const BAR_WIDTH: usize = 40;
+
+/// Shows the levels of the configured channels and aux channels, read from the
+/// interface's meters, until interrupted with Ctrl-C.
+pub fn meters(config: &Config) -> Result<(), Box<dyn Error>> {
+    let base_url = format!("http://{}", config.ip_address.address);
+    let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+
+    // clear the screen once, every refresh draws over it
+    print!("\x1b[2J");
+    // the datastore is read until the device answers, like the meters below
+    let layout = loop {
+        match datastore_keys(&client, &base_url) {
+            Ok(keys) => break Layout::from_keys(&keys),
+            Err(err) => {
+                print!(
+                    "\x1b[HLevels (Ctrl-C to stop)\x1b[K\nError reading the datastore: {}\x1b[K\n\x1b[J",
+                    err
+                );
+                stdout().flush()?;
+                thread::sleep(REFRESH_INTERVAL);
+            }
+        }
+    };
+
+    let mut channels: Vec<Meter> = config
+        .channels
+        .iter()
+        .map(|(index, name)| Meter::new(*index, name, layout.channel(*index)))
+        .collect();
+    let mut aux_channels: Vec<Meter> = config
+        .aux_channels
+        .iter()
+        .map(|(index, name)| Meter::new(*index, name, layout.aux(*index)))
+        .collect();
+
+    loop {
+        // a request that fails is shown in place of the status, polling carries on
+        let (levels, status) = match levels(&client, &base_url) {
+            Ok(levels) => (levels, String::new()),
+            Err(err) => (vec![], format!("Error reading the meters: {}", err)),
+        };
+
+        let now = Instant::now();
+        let mut screen = format!(
+            "\x1b[HLevels (Ctrl-C to stop)\x1b[K\n{}\x1b[K\nChannels:\x1b[K\n",
+            status
+        );
+        for meter in channels.iter_mut() {
+            screen.push_str(&meter.update(&levels, now));
+        }
+        screen.push_str("\x1b[K\nAux Channels:\x1b[K\n");
+        if levels.is_empty() || layout.matches(levels.len()) {
+            for meter in aux_channels.iter_mut() {
+                screen.push_str(&meter.update(&levels, now));
+            }
+        } else {
+            screen.push_str(&format!(
+                "    {} meters for {} buses, can't tell which are the aux channels\x1b[K\n",
+                levels.len(),
+                layout.buses()
+            ));
+        }
+        print!("{}\x1b[J", screen);
+        stdout().flush()?;
+        thread::sleep(REFRESH_INTERVAL);
+    }
+}
+
+fn get(client: &Client, url: &str) -> Result<Value, Box<dyn Error>> {
+    Ok(client.get(url).send()?.error_for_status()?.json()?)
+}
+
+/// The keys of the datastore, in the order the device sent them.
+fn datastore_keys(client: &Client, base_url: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let keys: DatastoreKeys = client
+        .get(format!("{}/datastore", base_url))
+        .send()?
+        .error_for_status()?
+        .json()?;
+    Ok(keys.0)
+}
+
+/// The current `mix/level` meters, one value per mixer bus.
+fn levels(client: &Client, base_url: &str) -> Result<Vec<f32>, Box<dyn Error>> {
+    let response = get(client, &format!("{}/meters?meters=mix/level", base_url))?;
+    Ok(response["mix/level"]
+        .as_array()
+        .ok_or("no mix/level meters in the response")?
+        .iter()
+        .map(|level| level.as_f64().unwrap_or_default() as f32)
+        .collect())
+}
+
+/// Where the buses of the mixer sit in the `mix/level` meter.
+///
+/// The meter has one level per bus, with the bus types in the order their
+/// `mix/<bus>/<index>/...` keys first appear in the datastore, e.g. channels, groups,
+/// aux channels, reverbs and the mains. If the number of levels doesn't add up the
+/// aux meters aren't shown, since their place in the meter isn't known.
+#[derive(Debug, PartialEq)]
+struct Layout {
+    /// Each bus type with its number of buses, in meter order.
+    buses: Vec<(String, usize)>,
+}
+
+impl Layout {
+    /// Counts the buses of each type, one more than the highest index of the keys.
+    fn from_keys(keys: &[String]) -> Layout {
+        let mut buses: Vec<(String, usize)> = vec![];
+        for key in keys {
+            let mut parts = key.split('/');
+            let (Some("mix"), Some(bus), Some(Ok(index))) = (
+                parts.next(),
+                parts.next(),
+                parts.next().map(str::parse::<usize>),
+            ) else {
+                continue;
+            };
+            match buses.iter_mut().find(|(name, _)| name == bus) {
+                Some((_, count)) => *count = (index + 1).max(*count),
+                None => buses.push((bus.to_string(), index + 1)),
+            }
+        }
+        Layout { buses }
+    }
+
+    fn buses(&self) -> usize {
+        self.buses.iter().map(|(_, count)| count).sum()
+    }
+
+    /// Whether a meter with `count` levels has the layout described above.
+    fn matches(&self, count: usize) -> bool {
+        count == self.buses()
+    }
+
+    /// The number of levels before the buses of type `bus`.
+    fn offset(&self, bus: &str) -> usize {
+        self.buses
+            .iter()
+            .take_while(|(name, _)| name != bus)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    fn channel(&self, index: usize) -> usize {
+        self.offset("chan") + index
+    }
+
+    fn aux(&self, index: usize) -> usize {
+        self.offset("aux") + index
+    }
+}
+
+/// The keys of a datastore response, its values aren't needed.
+struct DatastoreKeys(Vec<String>);
+
+impl<'de> Deserialize<'de> for DatastoreKeys {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Keys;
+
+        impl<'de> Visitor<'de> for Keys {
+            type Value = DatastoreKeys;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a datastore object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<DatastoreKeys, A::Error> {
+                let mut keys = vec![];
+                while let Some((key, IgnoredAny)) = map.next_entry::<String, IgnoredAny>()? {
+                    keys.push(key);
+                }
+                Ok(DatastoreKeys(keys))
+            }
+        }
+
+        deserializer.deserialize_map(Keys)
+    }
+}
+
+/// A labelled meter holding its peak for `PEAK_HOLD`.
+struct Meter {
+    label: String,
+    index: usize,
+    peak: f32,
+    peak_at: Instant,
+}
+
+impl Meter {
+    fn new(channel: usize, name: &str, index: usize) -> Meter {
+        Meter {
+            label: format!("{:>3} {}", channel, name),
+            index,
+            peak: 0.0,
+            peak_at: Instant::now(),
+        }
+    }
+
+    /// Takes the meter's level from `levels` and returns its line.
+    fn update(&mut self, levels: &[f32], now: Instant) -> String {
+        let level = levels.get(self.index).copied().unwrap_or_default();
+        if level >= self.peak || now.duration_since(self.peak_at) > PEAK_HOLD {
+            self.peak = level;
+            self.peak_at = now;
+        }
+        format!(
+            "{:<20} {:>6} {}\x1b[K\n",
+            self.label,
+            format_db(to_db(level)),
+            bar(to_db(level), to_db(self.peak))
+        )
+    }
+}
+
+fn to_db(level: f32) -> f32 {
+    if level > 0.0 {
+        20.0 * level.log10()
+    } else {
+        f32::NEG_INFINITY
+    }
+}
+
+fn format_db(db: f32) -> String {
+    if db.is_finite() {
+        format!("{:.1}", db)
+    } else {
+        String::from("-inf")
+    }
+}
+
+/// Draws `db` as a bar from `FLOOR_DB` to 0 dB, with the peak as a `|`.
+fn bar(db: f32, peak_db: f32) -> String {
+    let position = |db: f32| {
+        let fraction = ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0);
+        (fraction * BAR_WIDTH as f32).round() as usize
+    };
+    let filled = position(db);
+    let mut bar: Vec<char> = (0..BAR_WIDTH)
+        .map(|i| if i < filled { '#' } else { '.' })
+        .collect();
+    let peak = position(peak_db);
+    if peak > filled {
+        bar[peak - 1] = '|';
+    }
+    format!("[{}]", bar.into_iter().collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let keys: DatastoreKeys = serde_json::from_str(
+            r#"{
+                "mix/ctrls/dsp/usage": 10,
+                "mix/chan/0/matrix/fader": 1.0,
+                "mix/chan/47/matrix/fader": 1.0,
+                "mix/group/0/matrix/fader": 1.0,
+                "mix/aux/6/matrix/fader": 1.0,
+                "mix/reverb/1/matrix/fader": 1.0,
+                "mix/main/0/matrix/fader": 1.0,
+                "mix/main/1/matrix/fader": 1.0,
+                "ext/obank/0/ch/0/name": "Main L"
+            }"#,
+        )
+        .unwrap();
+        let layout = Layout::from_keys(&keys.0);
+        assert_eq!(
+            layout.buses,
+            vec![
+                (String::from("chan"), 48),
+                (String::from("group"), 1),
+                (String::from("aux"), 7),
+                (String::from("reverb"), 2),
+                (String::from("main"), 2),
+            ]
+        );
+        assert_eq!(layout.channel(2), 2);
+        // after the channels and the group
+        assert_eq!(layout.aux(4), 53);
+        assert!(layout.matches(60));
+        assert!(!layout.matches(57));
+    }
+
+    #[test]
+    fn test_peak_hold() {
+        let mut meter = Meter::new(2, "OT", 0);
+        let start = Instant::now();
+        meter.update(&[0.5], start);
+        meter.update(&[0.1], start + Duration::from_secs(1));
+        assert_eq!(meter.peak, 0.5);
+        meter.update(&[0.1], start + Duration::from_secs(3));
+        assert_eq!(meter.peak, 0.1);
+        // meters the device doesn't report are silent
+        assert!(meter.update(&[], start).contains("-inf"));
+    }
+
+    #[test]
+    fn test_bar() {
+        assert_eq!(bar(0.0, 0.0), format!("[{}]", "#".repeat(BAR_WIDTH)));
+        assert_eq!(
+            bar(f32::NEG_INFINITY, f32::NEG_INFINITY),
+            format!("[{}]", ".".repeat(BAR_WIDTH))
+        );
+        assert_eq!(
+            bar(-30.0, -15.0),
+            format!("[{}{}|{}]", "#".repeat(20), ".".repeat(9), ".".repeat(10))
+        );
+        assert_eq!(format_db(to_db(0.5)), "-6.0");
+    }
+}
@@ -1,2 +1,9 @@
+pub mod bridge;
+pub mod curve;
+pub mod listen;
+pub mod mapping;
 pub mod midicommand;
 pub mod miditype;
+pub mod notemode;
+pub mod queue;
+pub mod throttle;
@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::mapping::MidiMapping;
+use super::midicommand::MidiCommand;
+use super::miditype::MidiType;
+use super::queue::SendQueue;
+use crate::config::{Config, Reload};
+use crate::motu::journal::Journal;
+use crate::motu::Motu;
+
+/// The MIDI mappings of a config, their commands are sent by a `SendQueue` that
+/// may be shared with other inputs.
+pub struct Bridge {
+    midi_commands: Vec<MidiCommand>,
+    queue: Arc<SendQueue>,
+}
+
+impl Bridge {
+    pub fn new(config: &Config, queue: Arc<SendQueue>) -> Result<Bridge, Box<dyn Error>> {
+        Ok(Bridge {
+            midi_commands: midi_commands(config)?,
+            queue,
+        })
+    }
+
+    /// Queues the commands of every mapping matching the incoming message.
+    pub fn handle(&mut self, message: &[u8]) -> Result<(), Box<dyn Error>> {
+        // a message can match several mappings, e.g. a note-off releasing a momentary note-on
+        for midi_command in self
+            .midi_commands
+            .iter_mut()
+            .filter(|midi_command| midi_command.matches(message))
+        {
+            let commands = midi_command.process(message)?;
+            if !commands.is_empty() {
+                self.queue.push(commands);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Reload for Bridge {
+    type Prepared = Vec<MidiCommand>;
+
+    fn prepare(&self, config: &Config) -> Result<Vec<MidiCommand>, Box<dyn Error>> {
+        midi_commands(config)
+    }
+
+    /// Switches to the new mappings, those that didn't change keep their state.
+    fn apply(&mut self, mut midi_commands: Vec<MidiCommand>) {
+        let mut previous_commands: Vec<MidiCommand> = self.midi_commands.drain(..).collect();
+        for midi_command in midi_commands.iter_mut() {
+            if let Some(index) = previous_commands
+                .iter()
+                .position(|previous| previous.message == midi_command.message)
+            {
+                midi_command.inherit(previous_commands.swap_remove(index));
+            }
+        }
+        self.midi_commands = midi_commands;
+    }
+}
+
+/// The queue the bridges send through, paced by the `send_interval` of the config.
+pub(crate) fn send_queue(
+    config: &Config,
+    dry_run: bool,
+    journal: &Arc<Mutex<Journal>>,
+) -> Result<SendQueue, Box<dyn Error>> {
+    let send_interval = config
+        .midi_config
+        .as_ref()
+        .map_or(0, |midi_config| midi_config.send_interval);
+    Ok(SendQueue::new(
+        motu(config, dry_run, journal)?,
+        Duration::from_millis(send_interval),
+    ))
+}
+
+/// Connects to the interface at the address of the config, or only prints what
+/// would be sent for a dry run. Changes are recorded in `journal`.
+pub(crate) fn motu(
+    config: &Config,
+    dry_run: bool,
+    journal: &Arc<Mutex<Journal>>,
+) -> Result<Motu, Box<dyn Error>> {
+    let ip: &str = &config.ip_address.address.to_string();
+    let port = &config.ip_address.port.to_string();
+    let mut motu = if dry_run {
+        Motu::dry_run(ip, port, config)?
+    } else {
+        Motu::new(ip, port, config)?
+    };
+    motu.set_journal(journal.clone());
+    Ok(motu)
+}
+
+/// Builds the `MidiCommand`s for all `midi_mapping_*` tables of the config.
+pub fn midi_commands(config: &Config) -> Result<Vec<MidiCommand>, Box<dyn Error>> {
+    let midi_config = config
+        .midi_config
+        .as_ref()
+        .ok_or("no [midi_config] found in config file")?;
+    let midi_channel = midi_config.midi_channel - 1;
+
+    let mut midi_commands: Vec<MidiCommand> = Vec::new();
+    let tables: [(MidiType, &BTreeMap<usize, MidiMapping>); 3] = [
+        (MidiType::CC, &config.midi_mapping_cc),
+        (MidiType::NoteOn, &config.midi_mapping_note_on),
+        (MidiType::NoteOff, &config.midi_mapping_note_off),
+    ];
+    for (midi_type, mappings) in tables {
+        let status = midi_channel + (u8::from(midi_type) << 4);
+        midi_commands.extend(mappings.iter().map(|(key, mapping)| {
+            let midi_message: [u8; 3] = [status, *key as u8, 0];
+            MidiCommand::from_mapping(&midi_message, mapping, &midi_config.throttle).unwrap()
+        }));
+    }
+    Ok(midi_commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(cc_2: &str) -> Config {
+        Config::parse(&format!(
+            r#"
+            [network]
+            ip_address = "127.0.0.1:8000"
+            [midi_config]
+            input = "in"
+            output = "out"
+            midi_channel = 1
+            throttle = {{ deadband = 4 }}
+            [channels]
+            [aux_channels]
+            [monitor_groups]
+            [midi_mapping_cc]
+            1 = "vol(1)"
+            2 = "{}"
+            [midi_mapping_note_on]
+            60 = {{ command = "mute(1)", mode = "toggle" }}
+            [midi_mapping_note_off]
+            "#,
+            cc_2
+        ))
+        .unwrap()
+    }
+
+    fn bridge(config: &Config) -> Bridge {
+        Bridge::new(
+            config,
+            Arc::new(send_queue(config, true, &Default::default()).unwrap()),
+        )
+        .unwrap()
+    }
+
+    fn reload(bridge: &mut Bridge, config: &Config) -> Result<(), Box<dyn Error>> {
+        let prepared = bridge.prepare(config)?;
+        bridge.apply(prepared);
+        Ok(())
+    }
+
+    /// The number of the given messages the bridge lets through.
+    fn sent(bridge: &mut Bridge, messages: &[[u8; 3]]) -> usize {
+        messages
+            .iter()
+            .filter(|message| {
+                bridge
+                    .midi_commands
+                    .iter_mut()
+                    .filter(|midi_command| midi_command.matches(&message[..]))
+                    .any(|midi_command| !midi_command.process(&message[..]).unwrap().is_empty())
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_reload_keeps_state_of_unchanged_mappings() {
+        let mut bridge = bridge(&config("vol(2)"));
+        assert_eq!(
+            sent(
+                &mut bridge,
+                &[[0xB0, 1, 64], [0xB0, 2, 64], [0x90, 60, 100]]
+            ),
+            3
+        );
+
+        reload(&mut bridge, &config("vol(3)")).unwrap();
+        // within the deadband of the last send, and the toggle is still on
+        assert_eq!(sent(&mut bridge, &[[0xB0, 1, 65]]), 0);
+        let toggle = bridge
+            .midi_commands
+            .iter()
+            .find(|midi_command| midi_command.message[1] == 60)
+            .unwrap();
+        assert!(toggle.is_engaged());
+        // the changed mapping starts over
+        assert_eq!(sent(&mut bridge, &[[0xB0, 2, 65]]), 1);
+    }
+
+    #[test]
+    fn test_failed_reload_keeps_mappings() {
+        let mut bridge = bridge(&config("vol(2)"));
+        let mut unusable = config("vol(3)");
+        unusable.midi_config = None;
+        assert!(reload(&mut bridge, &unusable).is_err());
+        assert_eq!(bridge.midi_commands[1].motu_command.to_string(), "vol(2)");
+    }
+}
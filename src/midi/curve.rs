@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+/// Lowest level reached by the audio taper, anything below is treated as silence.
+const AUDIO_TAPER_FLOOR_DB: f32 = -60.0;
+
+pub trait EasingAlgorithm {
+    /// Maps a normalized input (0.0..=1.0) onto a normalized output.
+    fn easing(&self, x: f32) -> f32;
+}
+
+/// Response curve applied to the normalized MIDI value of a mapping.
+///
+/// In the config this is either a name, `curve = "linear"`, or a lookup table,
+/// `curve = { table = [0.0, 0.1, 0.5, 1.0] }`.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Curve {
+    Linear,
+    #[default]
+    Circular,
+    Exponential,
+    Logarithmic,
+    /// Spreads the input evenly over -60..0 dB and outputs the matching linear gain.
+    AudioTaper,
+    /// Evenly spaced output points, linearly interpolated.
+    Table(Vec<f32>),
+}
+
+impl EasingAlgorithm for Curve {
+    fn easing(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        match self {
+            Curve::Linear => x,
+            Curve::Circular => easing_circ(x),
+            Curve::Exponential => easing_exp(x),
+            Curve::Logarithmic => easing_log(x),
+            Curve::AudioTaper => easing_audio_taper(x),
+            Curve::Table(table) => easing_table(table, x),
+        }
+    }
+}
+
+fn easing_circ(x: f32) -> f32 {
+    1.0 - (1.0 - x * x).sqrt()
+}
+
+fn easing_exp(x: f32) -> f32 {
+    (2.0_f32.powf(10.0 * x) - 1.0) / 1023.0
+}
+
+fn easing_log(x: f32) -> f32 {
+    (1.0 + 1023.0 * x).log2() / 10.0
+}
+
+fn easing_audio_taper(x: f32) -> f32 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let db = AUDIO_TAPER_FLOOR_DB * (1.0 - x);
+    10.0_f32.powf(db / 20.0)
+}
+
+fn easing_table(table: &[f32], x: f32) -> f32 {
+    match table.len() {
+        0 => x,
+        1 => table[0],
+        len => {
+            let position = x * (len - 1) as f32;
+            let index = (position.floor() as usize).min(len - 2);
+            let fraction = position - index as f32;
+            table[index] + (table[index + 1] - table[index]) * fraction
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curve_endpoints() {
+        let curves = [
+            Curve::Linear,
+            Curve::Circular,
+            Curve::Exponential,
+            Curve::Logarithmic,
+            Curve::AudioTaper,
+            Curve::Table(vec![0.0, 0.2, 1.0]),
+        ];
+        for curve in curves {
+            assert!(curve.easing(0.0).abs() < 1e-6, "{:?} at 0.0", curve);
+            assert!((curve.easing(1.0) - 1.0).abs() < 1e-6, "{:?} at 1.0", curve);
+        }
+    }
+
+    #[test]
+    fn test_audio_taper_db() {
+        // halfway is -30 dB
+        let gain = Curve::AudioTaper.easing(0.5);
+        assert!((20.0 * gain.log10() + 30.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_table_interpolation() {
+        let curve = Curve::Table(vec![0.0, 0.2, 1.0]);
+        assert!((curve.easing(0.25) - 0.1).abs() < 1e-6);
+        assert!((curve.easing(0.75) - 0.6).abs() < 1e-6);
+    }
+}
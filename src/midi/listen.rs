@@ -0,0 +1,604 @@
+use std::error::Error;
+use std::io::{stdin, stdout, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiInputPort};
+
+use super::bridge::{self, Bridge};
+use super::miditype::MidiType;
+use super::queue::SendQueue;
+use crate::args::NetworkArgs;
+use crate::config::{self, edit::ConfigDocument, ConfigWatcher, Reload};
+use crate::motu::journal::Journal;
+use crate::motu::osc::OscServer;
+use crate::motu::MotuCommand;
+use crate::osc_bridge::OscBridge;
+
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+trait MidiMessage {
+    fn is_midi(&self) -> bool {
+        false
+    }
+
+    fn channel(&self) -> Option<u8> {
+        None
+    }
+
+    fn midi_type(&self) -> Option<MidiType> {
+        None
+    }
+}
+
+impl MidiMessage for &[u8] {
+    fn is_midi(&self) -> bool {
+        self.midi_type().is_some()
+    }
+
+    fn channel(&self) -> Option<u8> {
+        if self.is_midi() {
+            Some((self[0] & 0x0F) + 1)
+        } else {
+            None
+        }
+    }
+
+    fn midi_type(&self) -> Option<MidiType> {
+        match self {
+            &[midi_type, _, _] => Some(midi_type.into()),
+            _ => None,
+        }
+    }
+}
+
+/// Bridges the configured MIDI input and OSC server to the interface until 'Q' is
+/// entered. The config is reloaded whenever one of its files changes, `network` is
+/// laid over it every time. With `dry_run` the commands are printed instead of sent.
+pub fn listen(
+    config_file_name: &str,
+    profile: Option<&str>,
+    network: NetworkArgs,
+    dry_run: bool,
+) -> Result<(), Box<dyn Error>> {
+    let config = config::Config::build(config_file_name.to_string(), network, profile)?;
+    if config.midi_config.is_none() && config.osc_server.is_none() {
+        return Err("no [midi_config] or [osc_server] found in config file".into());
+    }
+
+    let journal = Arc::new(Mutex::new(Journal::open_default(dry_run)?));
+    let mut bridges = Bridges {
+        midi: None,
+        osc: None,
+        queue: Arc::new(bridge::send_queue(&config, dry_run, &journal)?),
+        journal,
+    };
+    let _conn_in = match &config.midi_config {
+        Some(midi_config) => {
+            let bridge = Arc::new(Mutex::new(Bridge::new(&config, bridges.queue.clone())?));
+            bridges.midi = Some(bridge.clone());
+            Some(connect_midi(&midi_config.input, bridge)?)
+        }
+        None => None,
+    };
+    if let Some(osc_server) = &config.osc_server {
+        let bridge = Arc::new(Mutex::new(OscBridge::new(&config, bridges.queue.clone())));
+        bridges.osc = Some(bridge.clone());
+        serve_osc(osc_server.port, bridge)?;
+    }
+    watch_config(
+        config_file_name,
+        profile,
+        network,
+        dry_run,
+        &config,
+        bridges,
+    );
+
+    println!("Listening (type 'Q' and hit enter to exit) ...");
+    let mut input = String::new();
+    loop {
+        input.clear();
+        stdin().read_line(&mut input)?; // wait for next enter key press
+        if input.contains('Q') || input.contains('q') {
+            break;
+        }
+    }
+
+    println!("Closing connection");
+    Ok(())
+}
+
+fn connect_midi(
+    midi_input_device: &str,
+    bridge: Arc<Mutex<Bridge>>,
+) -> Result<MidiInputConnection<()>, Box<dyn Error>> {
+    println!("Initializing midi...");
+    let mut midi_in = MidiInput::new("midir reading input")?;
+    midi_in.ignore(Ignore::None);
+    println!("Midi initialized.");
+
+    let in_port = select_input_port(&midi_in, midi_input_device)?;
+
+    println!("\nOpening connection");
+    let in_port_name = midi_in.port_name(&in_port)?;
+
+    let conn_in = midi_in.connect(
+        &in_port,
+        "midir-read-input",
+        move |_stamp, message, _| {
+            if message.is_midi() {
+                if let Err(err) = bridge.lock().unwrap().handle(message) {
+                    eprintln!("Error handling MIDI {:?}: {}", message, err);
+                }
+            }
+        },
+        (),
+    )?;
+    println!("Connection open, reading input from '{}'", in_port_name);
+    Ok(conn_in)
+}
+
+/// Receives OSC on `port` in the background. Changing the port needs a restart.
+fn serve_osc(port: u16, bridge: Arc<Mutex<OscBridge>>) -> Result<(), Box<dyn Error>> {
+    let server = OscServer::bind(port)?;
+    println!("Listening for OSC on port {}", port);
+    thread::spawn(move || loop {
+        let messages = match server.recv() {
+            Ok(messages) => messages,
+            Err(err) => {
+                eprintln!("Error receiving OSC: {}", err);
+                continue;
+            }
+        };
+        for message in messages {
+            if let Err(err) = bridge.lock().unwrap().handle(&message) {
+                eprintln!("{}: {}", message.addr, err);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Rebuilds the bridges whenever one of the config files changes. A config that fails
+/// to load is reported and the bridges keep running with the previous one.
+fn watch_config(
+    config_file_name: &str,
+    profile: Option<&str>,
+    network: NetworkArgs,
+    dry_run: bool,
+    config: &config::Config,
+    bridges: Bridges,
+) {
+    let config_file_name = config_file_name.to_string();
+    let profile = profile.map(String::from);
+    let mut watcher = ConfigWatcher::new(&config.files);
+    thread::spawn(move || loop {
+        thread::sleep(CONFIG_POLL_INTERVAL);
+        if !watcher.changed() {
+            continue;
+        }
+        match reload(
+            &config_file_name,
+            profile.as_deref(),
+            network,
+            dry_run,
+            &bridges,
+        ) {
+            Ok(config) => {
+                watcher.watch(&config.files);
+                println!("Reloaded {}", config_file_name)
+            }
+            Err(err) => eprintln!(
+                "Error reloading {}, keeping previous config: {}",
+                config_file_name, err
+            ),
+        }
+    });
+}
+
+/// The bridges `listen` runs and the queue they send through. The journal is kept
+/// across reloads, so changes made before one can still be undone.
+struct Bridges {
+    midi: Option<Arc<Mutex<Bridge>>>,
+    osc: Option<Arc<Mutex<OscBridge>>>,
+    queue: Arc<SendQueue>,
+    journal: Arc<Mutex<Journal>>,
+}
+
+impl Bridges {
+    /// Switches the bridges and the interface to `config`. Everything is built before
+    /// anything is switched, so if one of them can't use the config none of them does.
+    fn reload(&self, config: &config::Config, dry_run: bool) -> Result<(), Box<dyn Error>> {
+        let midi = prepare(&self.midi, config)?;
+        let osc = prepare(&self.osc, config)?;
+        let motu = bridge::motu(config, dry_run, &self.journal)?;
+
+        apply(&self.midi, midi);
+        apply(&self.osc, osc);
+        self.queue.replace_motu(motu);
+        Ok(())
+    }
+}
+
+fn prepare<R: Reload>(
+    bridge: &Option<Arc<Mutex<R>>>,
+    config: &config::Config,
+) -> Result<Option<R::Prepared>, Box<dyn Error>> {
+    bridge
+        .as_ref()
+        .map(|bridge| bridge.lock().unwrap().prepare(config))
+        .transpose()
+}
+
+fn apply<R: Reload>(bridge: &Option<Arc<Mutex<R>>>, prepared: Option<R::Prepared>) {
+    if let (Some(bridge), Some(prepared)) = (bridge, prepared) {
+        bridge.lock().unwrap().apply(prepared);
+    }
+}
+
+/// Builds the config again and hands it to the bridges. If it can't be loaded the
+/// bridges keep the previous one.
+fn reload(
+    config_file_name: &str,
+    profile: Option<&str>,
+    network: NetworkArgs,
+    dry_run: bool,
+    bridges: &Bridges,
+) -> Result<config::Config, Box<dyn Error>> {
+    let config = config::Config::build(config_file_name.to_string(), network, profile)?;
+    bridges.reload(&config, dry_run)?;
+    Ok(config)
+}
+
+/// Asks for target commands and maps each one to the next control moved on the controller.
+pub fn learn(config_file_name: &str, profile: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let mut config = config::Config::build(
+        config_file_name.to_string(),
+        NetworkArgs::default(),
+        profile,
+    )?;
+    let midi_config = config
+        .midi_config
+        .clone()
+        .ok_or("no [midi_config] found in config file")?;
+    let mut document = ConfigDocument::open(config_file_name)?;
+
+    let mut midi_in = MidiInput::new("midir learn input")?;
+    midi_in.ignore(Ignore::All);
+    let in_port = select_input_port(&midi_in, &midi_config.input)?;
+
+    let (sender, receiver) = mpsc::channel::<[u8; 3]>();
+    let _conn_in = midi_in.connect(
+        &in_port,
+        "midir-learn-input",
+        move |_stamp, message, _| {
+            if let [status, number, value] = *message {
+                let _ = sender.send([status, number, value]);
+            }
+        },
+        (),
+    )?;
+
+    let mut input = String::new();
+    loop {
+        print!("\nTarget command, e.g. vol(2) or send(OT,Space) (empty line to finish): ");
+        stdout().flush()?;
+        input.clear();
+        stdin().read_line(&mut input)?;
+        let command = input.trim().to_string();
+        if command.is_empty() {
+            break;
+        }
+        let learned = match MotuCommand::parse(&command, &config.channel_names()) {
+            Ok(learned) => learned,
+            Err(err) => {
+                println!("{}: {}", err, command);
+                continue;
+            }
+        };
+
+        // drop whatever the controller sent while the command was typed
+        while receiver.try_recv().is_ok() {}
+        println!("Move a control or hit a pad on the controller...");
+        let (message, midi_type) = loop {
+            let message = receiver.recv()?;
+            let midi_type = (&message[..]).midi_type();
+            match midi_type {
+                Some(MidiType::CC) => break (message, MidiType::CC),
+                Some(MidiType::NoteOn) if message[2] > 0 => break (message, MidiType::NoteOn),
+                _ => continue,
+            }
+        };
+
+        let channel = (&message[..]).channel().unwrap_or_default();
+        if channel != midi_config.midi_channel {
+            println!(
+                "Warning: received on MIDI channel {}, but the bridge listens on channel {}",
+                channel, midi_config.midi_channel
+            );
+        }
+
+        let table = config::mapping_table(&midi_type).ok_or("unsupported MIDI message")?;
+        let key = message[1] as usize;
+        if let Some((existing, origin)) = existing_mapping(&config, &document, &midi_type, key) {
+            print!(
+                "[{}] {} is already mapped to \"{}\"{}, replace it? [y/N] ",
+                table, key, existing, origin
+            );
+            stdout().flush()?;
+            input.clear();
+            stdin().read_line(&mut input)?;
+            if !input.trim().eq_ignore_ascii_case("y") {
+                println!("Kept \"{}\"", existing);
+                continue;
+            }
+        }
+        document.set_mapping(table, key, &command);
+        document.save()?;
+        println!(
+            "Mapped {} {} to \"{}\" in {}",
+            midi_type, key, command, config_file_name
+        );
+
+        // a profile is laid over the file, its mapping still wins
+        config = config::Config::build(
+            config_file_name.to_string(),
+            NetworkArgs::default(),
+            profile,
+        )?;
+        if let Some(mapping) = config.mappings(&midi_type).and_then(|m| m.get(&key)) {
+            if mapping.command != learned {
+                println!(
+                    "Warning: the profile still maps {} {} to \"{}\"",
+                    midi_type, key, mapping.command
+                );
+            }
+        }
+    }
+
+    println!("Closing connection");
+    Ok(())
+}
+
+/// The command `key` is already mapped to and where, the config file or an include
+/// or profile it is merged with.
+fn existing_mapping(
+    config: &config::Config,
+    document: &ConfigDocument,
+    midi_type: &MidiType,
+    key: usize,
+) -> Option<(String, &'static str)> {
+    let table = config::mapping_table(midi_type)?;
+    if let Some(command) = document.mapping(table, key) {
+        return Some((command, ""));
+    }
+    let mapping = config.mappings(midi_type)?.get(&key)?;
+    Some((mapping.command.to_string(), " in an include or the profile"))
+}
+
+/// Finds the input port matching the configured device name, asking on the console
+/// if there is no match and several ports are available.
+fn select_input_port(
+    midi_in: &MidiInput,
+    midi_input_device: &str,
+) -> Result<MidiInputPort, Box<dyn Error>> {
+    // Get an input port (read from console if multiple are available)
+    let in_ports = midi_in.ports();
+    for port in &in_ports {
+        println!("Found input port: {}", midi_in.port_name(port).unwrap());
+    }
+
+    // if midi_input_device exists in in_ports, then use that port, otherwise, use the match statement below
+    let in_port = match in_ports.iter().find(|port| {
+        midi_in
+            .port_name(port)
+            .unwrap()
+            .to_lowercase()
+            .contains(&midi_input_device.to_lowercase())
+    }) {
+        Some(port) => port,
+        None => {
+            println!(
+                "No MIDI input device found with name: {}",
+                midi_input_device
+            );
+            match in_ports.len() {
+                0 => return Err("no input port found".into()),
+                1 => {
+                    println!(
+                        "Choosing the only available input port: {}",
+                        midi_in.port_name(&in_ports[0]).unwrap()
+                    );
+                    &in_ports[0]
+                }
+                _ => {
+                    println!("\nAvailable input ports:");
+                    for (i, p) in in_ports.iter().enumerate() {
+                        println!("{}: {}", i, midi_in.port_name(p).unwrap());
+                    }
+                    print!("Please select input port: ");
+                    stdout().flush()?;
+                    let mut input = String::new();
+                    stdin().read_line(&mut input)?;
+                    in_ports
+                        .get(input.trim().parse::<usize>()?)
+                        .ok_or("invalid input port selected")?
+                }
+            }
+        }
+    };
+    Ok(in_port.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motu::transport::RecordingTransport;
+    use crate::motu::Motu;
+    use rosc::{OscMessage, OscType};
+
+    #[test]
+    fn test_existing_mapping_from_include() {
+        let directory = std::env::temp_dir().join(format!("motuman_learn_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join("base.toml"),
+            "[midi_mapping_cc]\n1 = \"vol(1)\"\n",
+        )
+        .unwrap();
+        let main = directory.join("main.toml");
+        std::fs::write(
+            &main,
+            "include = [\"base.toml\"]\n[network]\nip_address = \"127.0.0.1:8000\"\n\
+             [channels]\n[aux_channels]\n[monitor_groups]\n\
+             [midi_mapping_cc]\n2 = \"vol(2)\"\n\
+             [midi_mapping_note_on]\n[midi_mapping_note_off]\n",
+        )
+        .unwrap();
+        let main = main.to_str().unwrap();
+        let config = config::Config::build(main.to_string(), NetworkArgs::default(), None).unwrap();
+        let document = ConfigDocument::open(main).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let existing = |key| existing_mapping(&config, &document, &MidiType::CC, key);
+        assert_eq!(
+            existing(1),
+            Some((String::from("vol(1)"), " in an include or the profile"))
+        );
+        assert_eq!(existing(2), Some((String::from("vol(2)"), "")));
+        assert_eq!(existing(3), None);
+    }
+
+    #[test]
+    fn test_broken_config_is_not_loaded() {
+        let file = std::env::temp_dir().join(format!("motuman_reload_{}.toml", std::process::id()));
+        let config = |fader: &str| {
+            format!(
+                "[network]\nip_address = \"127.0.0.1:8000\"\ntransport = \"osc\"\n\
+                 [midi_config]\ninput = \"in\"\noutput = \"out\"\nmidi_channel = 1\n\
+                 [channels]\n[aux_channels]\n[monitor_groups]\n\
+                 [midi_mapping_cc]\n1 = \"{0}\"\n[midi_mapping_note_on]\n[midi_mapping_note_off]\n\
+                 [osc_server]\nport = 9000\nthrottle = \"none\"\n[osc_mapping]\nfader = \"{0}\"\n",
+                fader
+            )
+        };
+        let file_name = file.to_str().unwrap();
+        std::fs::write(&file, config("vol(2)")).unwrap();
+        let first =
+            config::Config::build(file_name.to_string(), NetworkArgs::default(), None).unwrap();
+        let osc = RecordingTransport::default();
+        let motu = Motu::with_transports(
+            Box::new(osc.clone()),
+            Box::new(RecordingTransport::default()),
+            &first,
+        )
+        .unwrap();
+        let queue = Arc::new(SendQueue::new(motu, Duration::ZERO));
+        let bridges = Bridges {
+            midi: Some(Arc::new(Mutex::new(
+                Bridge::new(&first, queue.clone()).unwrap(),
+            ))),
+            osc: Some(Arc::new(Mutex::new(OscBridge::new(&first, queue.clone())))),
+            queue,
+            journal: Default::default(),
+        };
+
+        std::fs::write(&file, config("vol(3)").replace("[channels]", "[channels")).unwrap();
+        let err = reload(file_name, None, NetworkArgs::default(), true, &bridges).unwrap_err();
+        assert!(err.to_string().contains(file_name), "{}", err);
+        std::fs::write(&file, config("vol(3")).unwrap();
+        let err = reload(file_name, None, NetworkArgs::default(), true, &bridges).unwrap_err();
+        assert!(err.to_string().contains("vol(3"), "{}", err);
+        // the OSC bridge could take this one, but the MIDI bridge can't
+        std::fs::write(&file, config("vol(3)").replace("[midi_config]", "[unused]")).unwrap();
+        assert!(reload(file_name, None, NetworkArgs::default(), true, &bridges).is_err());
+        std::fs::remove_file(&file).unwrap();
+
+        let message = OscMessage {
+            addr: String::from("/motuman/fader"),
+            args: vec![OscType::Float(1.0)],
+        };
+        bridges
+            .osc
+            .as_ref()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .handle(&message)
+            .unwrap();
+        drop(bridges);
+        let sent: Vec<String> = osc
+            .sent()
+            .concat()
+            .iter()
+            .map(MotuCommand::to_string)
+            .collect();
+        assert_eq!(sent, vec!["vol(2)"]);
+    }
+
+    #[test]
+    fn test_reload_keeps_the_journal() {
+        let file =
+            std::env::temp_dir().join(format!("motuman_journal_{}.toml", std::process::id()));
+        std::fs::write(
+            &file,
+            "[network]\nip_address = \"127.0.0.1:8000\"\n\
+             [channels]\n[aux_channels]\n[monitor_groups]\n\
+             [midi_mapping_cc]\n[midi_mapping_note_on]\n[midi_mapping_note_off]\n\
+             [osc_server]\nport = 9000\nthrottle = \"none\"\n",
+        )
+        .unwrap();
+        let file_name = file.to_str().unwrap();
+        let config =
+            config::Config::build(file_name.to_string(), NetworkArgs::default(), None).unwrap();
+        let journal: Arc<Mutex<Journal>> = Default::default();
+        journal.lock().unwrap().remember(
+            ["/mix/chan/2/matrix/fader", "/mix/chan/3/matrix/fader"]
+                .into_iter()
+                .map(|address| (address.to_string(), 0.5))
+                .collect(),
+        );
+        let queue = Arc::new(bridge::send_queue(&config, true, &journal).unwrap());
+        let bridges = Bridges {
+            midi: None,
+            osc: Some(Arc::new(Mutex::new(OscBridge::new(&config, queue.clone())))),
+            queue,
+            journal: journal.clone(),
+        };
+        let send = |address: &str| {
+            let message = OscMessage {
+                addr: address.to_string(),
+                args: vec![OscType::Float(1.0)],
+            };
+            bridges
+                .osc
+                .as_ref()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .handle(&message)
+                .unwrap();
+        };
+
+        send("/motuman/vol/2");
+        reload(file_name, None, NetworkArgs::default(), true, &bridges).unwrap();
+        send("/motuman/vol/3");
+        drop(bridges);
+        std::fs::remove_file(&file).unwrap();
+
+        let mut undone = vec![];
+        for _ in 0..2 {
+            journal
+                .lock()
+                .unwrap()
+                .undo(|commands| {
+                    undone.extend(commands.iter().map(MotuCommand::to_string));
+                    Ok(())
+                })
+                .unwrap();
+        }
+        assert_eq!(undone, vec!["vol(3)", "vol(2)"]);
+    }
+}
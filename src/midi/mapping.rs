@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::curve::{Curve, EasingAlgorithm};
+use super::notemode::NoteMode;
+use super::throttle::ThrottlePolicy;
+use crate::motu::MotuCommand;
+
+/// A single `midi_mapping_*` entry.
+///
+/// Entries can be written as a plain command, `34 = "vol(2)"`, or as an inline
+/// table with options, `34 = { command = "vol(2)", curve = "linear", range = [0.0, 0.5] }`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "MappingEntry")]
+pub struct MidiMapping {
+    pub command: MotuCommand,
+    pub options: MappingOptions,
+}
+
+/// Per-mapping shaping of the incoming value.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MappingOptions {
+    /// Defaults depend on the command, see `MappingOptions::for_command`.
+    #[serde(skip_serializing)]
+    pub curve: Curve,
+    /// Normalized `[min, max]` the controller's travel is spread over.
+    #[serde(skip_serializing_if = "is_full_range")]
+    pub range: [f32; 2],
+    #[serde(skip_serializing_if = "is_default")]
+    pub invert: bool,
+    /// Added after scaling into `range`, the result is clamped to `range`.
+    #[serde(skip_serializing_if = "is_default")]
+    pub offset: f32,
+    /// Overrides the global `[midi_config]` throttle for this mapping.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub throttle: Option<ThrottlePolicy>,
+    /// Switching behaviour of note mappings and CC buttons.
+    #[serde(skip_serializing_if = "is_default")]
+    pub mode: NoteMode,
+    /// Note-ons use their velocity as the value, or full scale if `false`.
+    #[serde(skip_serializing_if = "is_true")]
+    pub velocity: bool,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn is_full_range(range: &[f32; 2]) -> bool {
+    *range == [0.0, 1.0]
+}
+
+impl Default for MappingOptions {
+    fn default() -> Self {
+        MappingOptions {
+            curve: Curve::default(),
+            range: [0.0, 1.0],
+            invert: false,
+            offset: 0.0,
+            throttle: None,
+            mode: NoteMode::default(),
+            velocity: true,
+        }
+    }
+}
+
+impl MappingOptions {
+    /// The defaults for mappings to `command`. Pan is linear, so the centre of the
+    /// controller is the centre of the pan.
+    pub fn for_command(command: &MotuCommand) -> MappingOptions {
+        match command {
+            MotuCommand::Pan { .. } => MappingOptions {
+                curve: Curve::Linear,
+                ..Default::default()
+            },
+            _ => MappingOptions::default(),
+        }
+    }
+
+    /// Applies inversion, range and offset to a normalized value.
+    pub fn scale(&self, x: f32) -> f32 {
+        let [min, max] = self.range;
+        let x = if self.invert { 1.0 - x } else { x };
+        let x = min + x * (max - min) + self.offset;
+        x.clamp(min.min(max), max.max(min))
+    }
+
+    /// Maps a normalized value through `scale` and then the curve.
+    pub fn apply(&self, x: f32) -> f32 {
+        self.curve.easing(self.scale(x))
+    }
+}
+
+/// The table form is taken apart by hand, `flatten` would let misspelled options
+/// through unnoticed.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MappingEntry {
+    Command(String),
+    Table(toml::Table),
+}
+
+impl TryFrom<MappingEntry> for MidiMapping {
+    type Error = String;
+
+    fn try_from(entry: MappingEntry) -> Result<Self, Self::Error> {
+        match entry {
+            MappingEntry::Command(command) => {
+                Ok(MidiMapping::from(command.parse::<MotuCommand>()?))
+            }
+            MappingEntry::Table(mut table) => {
+                let command: MotuCommand = match table.remove("command") {
+                    Some(toml::Value::String(command)) => command.parse()?,
+                    Some(_) => return Err("command must be a string".to_string()),
+                    None => return Err("missing field `command`".to_string()),
+                };
+                let curve = table.remove("curve");
+                let mut options = MappingOptions::deserialize(toml::Value::Table(table))
+                    .map_err(|err| err.message().to_string())?;
+                options.curve = match curve {
+                    Some(curve) => {
+                        Curve::deserialize(curve).map_err(|err| err.message().to_string())?
+                    }
+                    None => MappingOptions::for_command(&command).curve,
+                };
+                Ok(MidiMapping { command, options })
+            }
+        }
+    }
+}
+
+/// Written back in the shorter of the two forms, a plain command if all options are default.
+impl Serialize for MidiMapping {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let defaults = MappingOptions::for_command(&self.command);
+        if self.options == defaults {
+            return serializer.serialize_str(&self.command.to_string());
+        }
+        #[derive(Serialize)]
+        struct Table<'a> {
+            command: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            curve: Option<&'a Curve>,
+            #[serde(flatten)]
+            options: &'a MappingOptions,
+        }
+        Table {
+            command: self.command.to_string(),
+            curve: Some(&self.options.curve).filter(|curve| **curve != defaults.curve),
+            options: &self.options,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl From<MotuCommand> for MidiMapping {
+    fn from(command: MotuCommand) -> Self {
+        MidiMapping {
+            options: MappingOptions::for_command(&command),
+            command,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_range_and_invert() {
+        let options = MappingOptions {
+            range: [0.0, 0.5],
+            ..Default::default()
+        };
+        assert_eq!(options.scale(1.0), 0.5);
+        assert_eq!(options.scale(0.5), 0.25);
+
+        let options = MappingOptions {
+            invert: true,
+            ..options
+        };
+        assert_eq!(options.scale(1.0), 0.0);
+        assert_eq!(options.scale(0.0), 0.5);
+    }
+
+    #[test]
+    fn test_offset_is_clamped_to_range() {
+        let options = MappingOptions {
+            range: [0.2, 0.8],
+            offset: 0.3,
+            ..Default::default()
+        };
+        assert_eq!(options.scale(0.0), 0.5);
+        assert_eq!(options.scale(1.0), 0.8);
+    }
+
+    #[test]
+    fn test_deserialize_table_entry() {
+        let mapping: MidiMapping = toml::from_str::<toml::Table>(
+            "m = { command = \"send(2,4)\", range = [0.0, 0.5], invert = true }",
+        )
+        .unwrap()["m"]
+            .clone()
+            .try_into()
+            .unwrap();
+        assert_eq!(mapping.options.range, [0.0, 0.5]);
+        assert!(mapping.options.invert);
+        assert_eq!(mapping.options.curve, Curve::Circular);
+    }
+
+    #[test]
+    fn test_misspelled_option_is_an_error() {
+        let table: toml::Table = toml::from_str(
+            r#"
+            range = { command = "vol(2)", rnage = [0.0, 0.5] }
+            curve = { command = "vol(2)", curv = "logarithmic" }
+            command = { comand = "vol(2)" }
+            "#,
+        )
+        .unwrap();
+        for (key, expected) in [
+            ("range", "rnage"),
+            ("curve", "curv"),
+            ("command", "missing field `command`"),
+        ] {
+            let err = MidiMapping::deserialize(table[key].clone()).unwrap_err();
+            assert!(err.to_string().contains(expected), "{}: {}", key, err);
+        }
+    }
+
+    #[test]
+    fn test_pan_centre() {
+        let table: toml::Table = toml::from_str(
+            r#"
+            plain = "pan(2)"
+            table = { command = "pan(2)", invert = true }
+            circular = { command = "pan(2)", curve = "circular" }
+            "#,
+        )
+        .unwrap();
+        let pan = |key: &str| -> MidiMapping { table[key].clone().try_into().unwrap() };
+        for key in ["plain", "table"] {
+            let mut command = pan(key).command;
+            command.set_value(pan(key).options.apply(64.0 / 127.0));
+            let MotuCommand::Pan { pan, .. } = command else {
+                panic!("{} is not a pan", key);
+            };
+            assert!(pan.abs() < 0.01, "{}: {}", key, pan);
+        }
+
+        // an explicit curve survives writing the mapping back
+        assert_eq!(
+            toml::Value::try_from(pan("circular")).unwrap(),
+            toml::Value::try_from(table["circular"].clone()).unwrap()
+        );
+        assert_eq!(
+            toml::Value::try_from(pan("plain")).unwrap(),
+            toml::Value::from("pan(2)")
+        );
+    }
+}
@@ -1,3 +1,7 @@
+use super::mapping::MidiMapping;
+use super::miditype::MidiType;
+use super::notemode::{NoteMode, LATCH_HOLD_MS};
+use super::throttle::{Throttle, ThrottlePolicy};
 use crate::motu::MotuCommand;
 
 #[derive(Debug)]
@@ -5,10 +9,13 @@ pub struct MidiCommand {
     // message field should be an array of 3 u8
     pub message: [u8; 3],
     midi_value: u8,
-    prev_midi_value: u8,
     pub motu_command: MotuCommand,
+    mapping: MidiMapping,
+    throttle_policy: ThrottlePolicy,
+    throttle: Box<dyn Throttle>,
     timestamp: u64,
-    prev_timestamp: u64,
+    engaged: bool,
+    pressed_at: u64,
 }
 impl MidiCommand {
     pub fn new(message: &[u8], motu_command: MotuCommand) -> Option<Self> {
@@ -17,64 +24,158 @@ impl MidiCommand {
             message_array.copy_from_slice(message);
             Some(Self {
                 message: message_array,
+                mapping: MidiMapping::from(motu_command.clone()),
                 motu_command,
-                timestamp: 10000,
+                throttle_policy: ThrottlePolicy::default(),
+                throttle: ThrottlePolicy::default().build(),
+                timestamp: 0,
                 midi_value: 0,
-                prev_midi_value: 127,
-                prev_timestamp: 0,
+                engaged: false,
+                pressed_at: 0,
             })
         } else {
             None
         }
     }
 
-    pub fn delta_value(&self) -> f32 {
-        // abs delta value
-        (self.midi_value as f32 - self.prev_midi_value as f32).abs()
+    /// Builds the command for a config mapping, `default_throttle` is used unless
+    /// the mapping sets its own policy.
+    pub fn from_mapping(
+        message: &[u8],
+        mapping: &MidiMapping,
+        default_throttle: &ThrottlePolicy,
+    ) -> Option<Self> {
+        let mut midi_command = Self::new(message, mapping.command.clone())?;
+        midi_command.mapping = mapping.clone();
+        midi_command.throttle_policy = mapping
+            .options
+            .throttle
+            .clone()
+            .unwrap_or_else(|| default_throttle.clone());
+        midi_command.throttle = midi_command.throttle_policy.build();
+        Some(midi_command)
     }
 
-    pub fn delta_time(&self) -> u64 {
-        self.timestamp - self.prev_timestamp
+    /// Takes over the runtime state (last value, throttle and switch state) of a
+    /// command built from the same mapping, so a config reload doesn't reset it.
+    /// Returns `false` and drops `previous` if the mapping changed.
+    pub fn inherit(&mut self, previous: MidiCommand) -> bool {
+        if previous.message != self.message
+            || previous.mapping != self.mapping
+            || previous.throttle_policy != self.throttle_policy
+        {
+            return false;
+        }
+        self.motu_command = previous.motu_command;
+        self.midi_value = previous.midi_value;
+        self.throttle = previous.throttle;
+        self.timestamp = previous.timestamp;
+        self.engaged = previous.engaged;
+        self.pressed_at = previous.pressed_at;
+        true
     }
 
-    /// Determines whether the MIDI command should be throttled based on the delta time and delta value.
-    /// Returns `true` if the command should be throttled, `false` otherwise.
-    pub fn do_throttle(&mut self) -> bool {
-        let delta_time = self.delta_time();
-        let delta_value = {
-            if delta_time > 1000 {
-                1000.0
-            } else {
-                self.delta_value()
+    /// Returns `true` if the incoming message belongs to this command. Switching
+    /// note-on mappings also listen to the note-off of their note.
+    pub fn matches(&self, message: &[u8]) -> bool {
+        if message.len() != 3 || message[1] != self.message[1] {
+            return false;
+        }
+        if message[0] == self.message[0] {
+            return true;
+        }
+        let same_channel = message[0] & 0x0F == self.message[0] & 0x0F;
+        same_channel
+            && self.mapping.options.mode.is_switch()
+            && matches!(MidiType::from(&self.message[0]), MidiType::NoteOn)
+            && matches!(MidiType::from(&message[0]), MidiType::NoteOff)
+    }
+
+    /// Feeds an incoming message through the mapping and returns the commands to run.
+    pub fn process(&mut self, message: &[u8]) -> Result<Vec<MotuCommand>, String> {
+        let is_note = !matches!(MidiType::from(&self.message[0]), MidiType::CC);
+        if !self.mapping.options.mode.is_switch() {
+            if !is_note {
+                self.set_midi_value(message[2])?;
+                return Ok(if self.do_throttle() {
+                    self.motu_commands()
+                } else {
+                    vec![]
+                });
+            }
+            // notes are discrete events and are never throttled
+            self.set_midi_value(self.note_value(message))?;
+            return Ok(self.motu_commands());
+        }
+
+        let now = now_millis()?;
+        // a note-on with velocity 0 is a note-off, CC buttons send 0 on release
+        let pressed = matches!(MidiType::from(&message[0]), MidiType::NoteOn | MidiType::CC)
+            && message[2] > 0;
+        let engaged = match (self.mapping.options.mode, pressed) {
+            (NoteMode::Toggle, true) | (NoteMode::Latch, true) => !self.engaged,
+            (NoteMode::Momentary, pressed) => pressed,
+            (NoteMode::Latch, false) if now.saturating_sub(self.pressed_at) >= LATCH_HOLD_MS => {
+                false
             }
+            _ => return Ok(vec![]),
         };
+        if pressed {
+            self.pressed_at = now;
+        }
+        if engaged == self.engaged {
+            return Ok(vec![]);
+        }
+        self.engaged = engaged;
+        let level = self.note_value(message) as f32 / 127.0;
+        Ok(self.switched_command(engaged, level).into_iter().collect())
+    }
 
-        if (100 >= delta_time && delta_time > 10 && delta_value > 5.0)
-            || (150 >= delta_time && delta_time > 100 && delta_value > 2.0)
-            || (250 >= delta_time && delta_time > 150 && delta_value > 0.0)
-            || (delta_time > 250 && delta_value > 0.0)
-            || delta_value > 30.0
-            || self.midi_value <= 2
-            || self.midi_value >= 125
-        {
-            // if true, set the prev_value and prev_time to the current values
-            self.prev_midi_value = self.midi_value;
-            self.prev_timestamp = self.timestamp;
-            true
+    /// Returns whether a switching note mapping is currently engaged.
+    pub fn is_engaged(&self) -> bool {
+        self.engaged
+    }
+
+    /// The MIDI value a note stands for: 0 for a note-off, whatever its release
+    /// velocity, otherwise the velocity, or full scale if the mapping ignores it.
+    fn note_value(&self, message: &[u8]) -> u8 {
+        let note_off = matches!(MidiType::from(&message[0]), MidiType::NoteOff) || message[2] == 0;
+        if note_off {
+            0
+        } else if self.mapping.options.velocity {
+            message[2]
         } else {
-            false
+            127
         }
     }
 
+    fn switched_command(&self, engaged: bool, level: f32) -> Option<MotuCommand> {
+        let mut command = self.motu_command.clone();
+        if command.has_value() {
+            command.set_value(
+                self.mapping
+                    .options
+                    .apply(if engaged { level } else { 0.0 }),
+            );
+            Some(command)
+        } else if engaged {
+            Some(command)
+        } else {
+            command.inverse()
+        }
+    }
+
+    /// Determines whether the MIDI command should be throttled, using the throttle policy of the mapping.
+    /// Returns `true` if the command should be sent, `false` otherwise.
+    pub fn do_throttle(&mut self) -> bool {
+        self.throttle.allow(self.midi_value as f32, self.timestamp)
+    }
+
     pub fn set_midi_value(&mut self, midi_value: u8) -> Result<(), String> {
         self.midi_value = midi_value;
-        self.prev_timestamp = self.timestamp;
-        self.timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map_err(|e| e.to_string())?
-            .as_millis() as u64;
+        self.timestamp = now_millis()?;
         self.motu_command
-            .set_value(easing_circ(midi_value as f32 / 127.0));
+            .set_value(self.mapping.options.apply(midi_value as f32 / 127.0));
         Ok(())
     }
 
@@ -83,10 +184,95 @@ impl MidiCommand {
     }
 }
 
-trait EasingAlgorithm {
-    fn easing(x: f32) -> f32;
+pub(crate) fn now_millis() -> Result<u64, String> {
+    Ok(std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_millis() as u64)
 }
 
-fn easing_circ(x: f32) -> f32 {
-    1.0 - (1.0 - x * x).sqrt()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::curve::Curve;
+    use crate::midi::mapping::MidiMapping;
+
+    fn note_command(command: &str, mode: NoteMode) -> MidiCommand {
+        let mut mapping = MidiMapping::from(command.parse::<MotuCommand>().unwrap());
+        mapping.options.mode = mode;
+        MidiCommand::from_mapping(&[0x90, 60, 0], &mapping, &ThrottlePolicy::None).unwrap()
+    }
+
+    #[test]
+    fn test_toggle_mute() {
+        let mut midi_command = note_command("mute(2)", NoteMode::Toggle);
+        let mute = "mute(2)".parse::<MotuCommand>().unwrap();
+        let unmute = "unmute(2)".parse::<MotuCommand>().unwrap();
+        assert_eq!(midi_command.process(&[0x90, 60, 100]), Ok(vec![mute]));
+        assert_eq!(midi_command.process(&[0x80, 60, 0]), Ok(vec![]));
+        assert_eq!(midi_command.process(&[0x90, 60, 100]), Ok(vec![unmute]));
+    }
+
+    #[test]
+    fn test_cc_button_toggle() {
+        let mut mapping = MidiMapping::from("mute(2)".parse::<MotuCommand>().unwrap());
+        mapping.options.mode = NoteMode::Toggle;
+        let mut midi_command =
+            MidiCommand::from_mapping(&[0xB0, 20, 0], &mapping, &ThrottlePolicy::None).unwrap();
+        let mute = "mute(2)".parse::<MotuCommand>().unwrap();
+        let unmute = "unmute(2)".parse::<MotuCommand>().unwrap();
+        assert_eq!(midi_command.process(&[0xB0, 20, 127]), Ok(vec![mute]));
+        assert_eq!(midi_command.process(&[0xB0, 20, 0]), Ok(vec![]));
+        assert_eq!(midi_command.process(&[0xB0, 20, 127]), Ok(vec![unmute]));
+    }
+
+    #[test]
+    fn test_note_velocity() {
+        let mut mapping = MidiMapping::from("send(2,4)".parse::<MotuCommand>().unwrap());
+        mapping.options.curve = Curve::Linear;
+        let mut midi_command =
+            MidiCommand::from_mapping(&[0x90, 60, 0], &mapping, &ThrottlePolicy::None).unwrap();
+        let commands = midi_command.process(&[0x90, 60, 127]).unwrap();
+        assert_eq!(commands[0].osc_command().unwrap().1, "1");
+        let commands = midi_command.process(&[0x90, 60, 0]).unwrap();
+        assert_eq!(commands[0].osc_command().unwrap().1, "0");
+
+        mapping.options.velocity = false;
+        let mut midi_command =
+            MidiCommand::from_mapping(&[0x90, 60, 0], &mapping, &ThrottlePolicy::None).unwrap();
+        let commands = midi_command.process(&[0x90, 60, 64]).unwrap();
+        assert_eq!(commands[0].osc_command().unwrap().1, "1");
+        // repeated hits are not throttled away
+        let commands = midi_command.process(&[0x90, 60, 127]).unwrap();
+        assert_eq!(commands.len(), 1);
+    }
+
+    #[test]
+    fn test_momentary_listens_to_note_off() {
+        let mut midi_command = note_command("mute(2)", NoteMode::Momentary);
+        assert!(midi_command.matches(&[0x80, 60, 0]));
+        assert!(!midi_command.matches(&[0x81, 60, 0]));
+        assert_eq!(midi_command.process(&[0x90, 60, 100]).unwrap().len(), 1);
+        assert!(midi_command.is_engaged());
+        // note-on with velocity 0 releases as well
+        assert_eq!(midi_command.process(&[0x90, 60, 0]).unwrap().len(), 1);
+        assert!(!midi_command.is_engaged());
+    }
+
+    #[test]
+    fn test_note_off_mapping() {
+        let mapping = MidiMapping::from("send(31,4)".parse::<MotuCommand>().unwrap());
+        let mut midi_command =
+            MidiCommand::from_mapping(&[0x80, 60, 0], &mapping, &ThrottlePolicy::None).unwrap();
+        // controllers often send a release velocity of 64
+        let commands = midi_command.process(&[0x80, 60, 64]).unwrap();
+        assert_eq!(commands[0].osc_command().unwrap().1, "0");
+
+        let mut mapping = mapping;
+        mapping.options.velocity = false;
+        let mut midi_command =
+            MidiCommand::from_mapping(&[0x80, 60, 0], &mapping, &ThrottlePolicy::None).unwrap();
+        let commands = midi_command.process(&[0x80, 60, 64]).unwrap();
+        assert_eq!(commands[0].osc_command().unwrap().1, "0");
+    }
 }
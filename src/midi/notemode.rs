@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// How long a latch pad has to be held before its release turns it off again.
+pub const LATCH_HOLD_MS: u64 = 500;
+
+/// Behaviour of a note mapping or CC button, set with `mode = "toggle"` and friends.
+/// A CC above 0 counts as a press, 0 as the release.
+///
+/// Switching modes send the command as written when engaged and its opposite
+/// when released: mute/unmute and monitor on/off swap, vol and send go to the
+/// bottom of their range.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteMode {
+    /// Runs the command on every message, the way CC mappings do.
+    #[default]
+    Trigger,
+    /// Flips between engaged and released on each note-on.
+    Toggle,
+    /// Engaged while the note is held.
+    Momentary,
+    /// A short press latches until the next press, a long hold acts momentary.
+    Latch,
+}
+
+impl NoteMode {
+    pub fn is_switch(&self) -> bool {
+        *self != NoteMode::Trigger
+    }
+}
@@ -0,0 +1,208 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::motu::{Motu, MotuCommand};
+
+/// Sends commands to the interface from a thread of its own, so a slow network never
+/// holds up MIDI input.
+///
+/// Commands waiting to be sent keep only the latest value per address: a fader moved
+/// while the previous batch is still on its way ends up as a single write. Batches go
+/// out at most once per `interval`, errors are reported and the queue carries on.
+pub struct SendQueue {
+    shared: Arc<(Mutex<Shared>, Condvar)>,
+    sender: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct Shared {
+    pending: Pending,
+    /// Replaces the interface of the sender thread before the next batch.
+    motu: Option<Motu>,
+    closed: bool,
+}
+
+impl SendQueue {
+    pub fn new(motu: Motu, interval: Duration) -> SendQueue {
+        let shared = Arc::new((Mutex::new(Shared::default()), Condvar::new()));
+        let sender = {
+            let shared = shared.clone();
+            thread::spawn(move || send_loop(motu, interval, &shared))
+        };
+        SendQueue {
+            shared,
+            sender: Some(sender),
+        }
+    }
+
+    /// Queues `commands` without waiting for them to be sent.
+    pub fn push(&self, commands: Vec<MotuCommand>) {
+        let (state, wake) = &*self.shared;
+        let mut state = state.lock().unwrap();
+        for command in commands {
+            state.pending.push(command);
+        }
+        wake.notify_one();
+    }
+
+    /// Sends the following batches to `motu`, e.g. after the config was reloaded.
+    pub fn replace_motu(&self, motu: Motu) {
+        let (state, wake) = &*self.shared;
+        state.lock().unwrap().motu = Some(motu);
+        wake.notify_one();
+    }
+}
+
+impl Drop for SendQueue {
+    /// Sends what is still queued before the sender thread ends.
+    fn drop(&mut self) {
+        let (state, wake) = &*self.shared;
+        state.lock().unwrap().closed = true;
+        wake.notify_one();
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.join();
+        }
+    }
+}
+
+fn send_loop(mut motu: Motu, interval: Duration, shared: &(Mutex<Shared>, Condvar)) {
+    let (state, wake) = shared;
+    prefetch(&motu);
+    loop {
+        let commands = {
+            let mut state = state.lock().unwrap();
+            while state.pending.is_empty() && !state.closed {
+                state = wake.wait(state).unwrap();
+            }
+            if let Some(replacement) = state.motu.take() {
+                motu = replacement;
+                prefetch(&motu);
+            }
+            if state.pending.is_empty() {
+                return;
+            }
+            state.pending.take()
+        };
+        let started = Instant::now();
+        if let Err(err) = motu.run(&commands) {
+            eprintln!("Error sending to the interface: {}", err);
+        }
+        thread::sleep(interval.saturating_sub(started.elapsed()));
+    }
+}
+
+/// Reads the values undo needs up front, instead of on the first move of each control.
+fn prefetch(motu: &Motu) {
+    if let Err(err) = motu.prefetch() {
+        eprintln!(
+            "Error reading the current values, changes may not be undoable: {}",
+            err
+        );
+    }
+}
+
+/// Commands waiting to be sent, in the order they arrived.
+#[derive(Default)]
+struct Pending {
+    commands: Vec<MotuCommand>,
+}
+
+impl Pending {
+    /// Adds a command, replacing a queued write to the same address. Commands
+    /// without an address, e.g. `init` or `undo`, keep their place and nothing is
+    /// coalesced across them.
+    fn push(&mut self, command: MotuCommand) {
+        if let Some((address, _)) = command.osc_command() {
+            let queued = self
+                .commands
+                .iter_mut()
+                .rev()
+                .take_while(|queued| queued.osc_command().is_some())
+                .find(|queued| queued.osc_command().is_some_and(|(a, _)| a == address));
+            if let Some(queued) = queued {
+                *queued = command;
+                return;
+            }
+        }
+        self.commands.push(command);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    fn take(&mut self) -> Vec<MotuCommand> {
+        std::mem::take(&mut self.commands)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::motu::transport::RecordingTransport;
+
+    fn vol(volume: f32) -> MotuCommand {
+        MotuCommand::parse_assignment(&format!("vol(2)={}", volume), &Default::default()).unwrap()
+    }
+
+    #[test]
+    fn test_pending_keeps_latest_value() {
+        let mut pending = Pending::default();
+        for command in [
+            vol(0.1),
+            "mute(2)".parse().unwrap(),
+            vol(0.2),
+            "unmute(2)".parse().unwrap(),
+            MotuCommand::Undo,
+            vol(0.3),
+            vol(0.4),
+        ] {
+            pending.push(command);
+        }
+        assert_eq!(
+            pending.take(),
+            vec![
+                vol(0.2),
+                "unmute(2)".parse().unwrap(),
+                MotuCommand::Undo,
+                vol(0.4),
+            ]
+        );
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_queue_sends_on_drop() {
+        let config = Config::parse(
+            r#"
+            [network]
+            ip_address = "127.0.0.1:8000"
+            transport = "osc"
+            [channels]
+            [aux_channels]
+            [monitor_groups]
+            [midi_mapping_cc]
+            [midi_mapping_note_on]
+            [midi_mapping_note_off]
+            "#,
+        )
+        .unwrap();
+        let osc = RecordingTransport::default();
+        let motu = Motu::with_transports(
+            Box::new(osc.clone()),
+            Box::new(RecordingTransport::default()),
+            &config,
+        )
+        .unwrap();
+        let queue = SendQueue::new(motu, Duration::from_millis(50));
+        for volume in [0.1, 0.2, 0.3] {
+            queue.push(vec![vol(volume)]);
+        }
+        drop(queue);
+        let sent = osc.sent().concat();
+        assert_eq!(sent.last(), Some(&vol(0.3)));
+        assert!(sent.len() <= 2);
+    }
+}
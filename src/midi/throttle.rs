@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+/// Decides which incoming values of a mapping are passed on to the interface.
+///
+/// Values are on the 0..=127 MIDI scale and timestamps are in milliseconds.
+pub trait Throttle: Debug + Send {
+    /// Returns `true` if the value should be sent.
+    fn allow(&mut self, value: f32, timestamp: u64) -> bool;
+}
+
+/// Throttling policy as written in the config, either globally in `[midi_config]`
+/// or per mapping: `throttle = "none"`, `"adaptive"`, `{ fixed_rate = 20 }` or `{ deadband = 2 }`.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThrottlePolicy {
+    None,
+    #[default]
+    Adaptive,
+    /// Minimum number of milliseconds between two sends.
+    FixedRate(u64),
+    /// Minimum change in MIDI steps since the last send.
+    Deadband(f32),
+}
+
+impl ThrottlePolicy {
+    pub fn build(&self) -> Box<dyn Throttle> {
+        match self {
+            ThrottlePolicy::None => Box::new(NoThrottle),
+            ThrottlePolicy::Adaptive => Box::<AdaptiveThrottle>::default(),
+            ThrottlePolicy::FixedRate(interval) => Box::new(FixedRateThrottle::new(*interval)),
+            ThrottlePolicy::Deadband(width) => Box::new(DeadbandThrottle::new(*width)),
+        }
+    }
+}
+
+fn is_edge(value: f32) -> bool {
+    value <= 2.0 || value >= 125.0
+}
+
+/// Sends every value.
+#[derive(Debug)]
+pub struct NoThrottle;
+
+impl Throttle for NoThrottle {
+    fn allow(&mut self, _value: f32, _timestamp: u64) -> bool {
+        true
+    }
+}
+
+/// Sends at most one value per interval, the ends of the range always pass.
+#[derive(Debug)]
+pub struct FixedRateThrottle {
+    interval: u64,
+    last_sent: Option<u64>,
+}
+
+impl FixedRateThrottle {
+    pub fn new(interval: u64) -> Self {
+        FixedRateThrottle {
+            interval,
+            last_sent: None,
+        }
+    }
+}
+
+impl Throttle for FixedRateThrottle {
+    fn allow(&mut self, value: f32, timestamp: u64) -> bool {
+        let due = match self.last_sent {
+            Some(last_sent) => timestamp.saturating_sub(last_sent) >= self.interval,
+            None => true,
+        };
+        if due || is_edge(value) {
+            self.last_sent = Some(timestamp);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Hysteresis filter for noisy pots, only passes changes of at least `width` steps.
+#[derive(Debug)]
+pub struct DeadbandThrottle {
+    width: f32,
+    last_sent: Option<f32>,
+}
+
+impl DeadbandThrottle {
+    pub fn new(width: f32) -> Self {
+        DeadbandThrottle {
+            width,
+            last_sent: None,
+        }
+    }
+}
+
+impl Throttle for DeadbandThrottle {
+    fn allow(&mut self, value: f32, _timestamp: u64) -> bool {
+        let pass = match self.last_sent {
+            Some(last_sent) => {
+                (value - last_sent).abs() >= self.width || (is_edge(value) && value != last_sent)
+            }
+            None => true,
+        };
+        if pass {
+            self.last_sent = Some(value);
+        }
+        pass
+    }
+}
+
+/// Lets more values through the slower the controller moves.
+#[derive(Debug)]
+pub struct AdaptiveThrottle {
+    prev_value: f32,
+    prev_timestamp: u64,
+}
+
+impl Default for AdaptiveThrottle {
+    fn default() -> Self {
+        AdaptiveThrottle {
+            prev_value: 127.0,
+            prev_timestamp: 0,
+        }
+    }
+}
+
+impl Throttle for AdaptiveThrottle {
+    fn allow(&mut self, value: f32, timestamp: u64) -> bool {
+        let delta_time = timestamp.saturating_sub(self.prev_timestamp);
+        self.prev_timestamp = timestamp;
+        let delta_value = {
+            if delta_time > 1000 {
+                1000.0
+            } else {
+                (value - self.prev_value).abs()
+            }
+        };
+
+        if (100 >= delta_time && delta_time > 10 && delta_value > 5.0)
+            || (150 >= delta_time && delta_time > 100 && delta_value > 2.0)
+            || (250 >= delta_time && delta_time > 150 && delta_value > 0.0)
+            || (delta_time > 250 && delta_value > 0.0)
+            || delta_value > 30.0
+            || is_edge(value)
+        {
+            self.prev_value = value;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_rate() {
+        let mut throttle = FixedRateThrottle::new(20);
+        assert!(throttle.allow(64.0, 1000));
+        assert!(!throttle.allow(65.0, 1010));
+        assert!(throttle.allow(66.0, 1020));
+        assert!(throttle.allow(127.0, 1021));
+    }
+
+    #[test]
+    fn test_deadband() {
+        let mut throttle = DeadbandThrottle::new(3.0);
+        assert!(throttle.allow(64.0, 0));
+        assert!(!throttle.allow(65.0, 0));
+        assert!(!throttle.allow(62.0, 0));
+        assert!(throttle.allow(67.0, 0));
+        assert!(throttle.allow(0.0, 0));
+        assert!(!throttle.allow(0.0, 0));
+    }
+
+    #[test]
+    fn test_adaptive_slow_moves_pass() {
+        let mut throttle = AdaptiveThrottle::default();
+        assert!(throttle.allow(64.0, 10_000));
+        assert!(!throttle.allow(65.0, 10_050));
+        assert!(throttle.allow(66.0, 10_250));
+    }
+}
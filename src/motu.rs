@@ -1,22 +1,24 @@
 use crate::config::Config;
 use crate::motu::channel::Channel;
 use crate::motu::channel::ChannelType;
-use reqwest::{
-    blocking::{Client, Response},
-    header,
+use crate::motu::journal::Journal;
+use crate::motu::transport::{
+    HttpTransport, OscTransport, PrintingTransport, Transport, TransportKind, TransportPolicy,
 };
 use rosc::OscMessage;
-use rosc::OscPacket;
 use rosc::OscType;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::sync::{Arc, Mutex};
 
 pub use self::motucommand::MotuCommand;
 
 pub mod channel;
 pub mod motucommand;
 
-mod osc;
+pub mod journal;
+pub mod osc;
+pub mod transport;
 
 pub trait OscSender {
     fn new(address: &str, value: f32) -> Self;
@@ -32,11 +34,14 @@ impl OscSender for OscMessage {
 }
 
 pub struct Motu {
-    http_client_url: String,
-    client: osc::OscClient,
-    aux_channels: HashMap<usize, String>,
-    channels: HashMap<usize, String>,
-    monitor_groups: HashMap<usize, String>,
+    osc: Box<dyn Transport>,
+    http: Box<dyn Transport>,
+    policy: TransportPolicy,
+    journal: Arc<Mutex<Journal>>,
+    aux_channels: BTreeMap<usize, String>,
+    channels: BTreeMap<usize, String>,
+    monitor_groups: BTreeMap<usize, String>,
+    macros: BTreeMap<String, Vec<MotuCommand>>,
 }
 
 pub fn json_payload(commands: &[MotuCommand]) -> String {
@@ -52,54 +57,128 @@ pub fn json_payload(commands: &[MotuCommand]) -> String {
 }
 impl Motu {
     pub fn new(ip: &str, port: &str, config: &Config) -> Result<Motu, Box<dyn Error>> {
-        let client = osc::OscClient::new(&format!("{}:{}", ip, port))?;
-        let http_client_url = format!("http://{}/datastore", ip);
+        let osc = OscTransport::new(&format!("{}:{}", ip, port))?;
+        let http = HttpTransport::new(ip)?;
+        Motu::with_transports(Box::new(osc), Box::new(http), config)
+    }
+
+    /// Builds a `Motu` that prints the OSC messages and HTTP requests instead of
+    /// sending them.
+    pub fn dry_run(ip: &str, port: &str, config: &Config) -> Result<Motu, Box<dyn Error>> {
+        let osc = PrintingTransport::osc(&format!("{}:{}", ip, port));
+        let http = PrintingTransport::http(ip);
+        Motu::with_transports(Box::new(osc), Box::new(http), config)
+    }
+
+    /// Builds a `Motu` sending through the given transports, chosen between by the
+    /// `transport` policy of the config.
+    pub fn with_transports(
+        osc: Box<dyn Transport>,
+        http: Box<dyn Transport>,
+        config: &Config,
+    ) -> Result<Motu, Box<dyn Error>> {
         Ok(Motu {
-            http_client_url,
-            client,
+            osc,
+            http,
+            policy: config.network.transport.clone(),
+            journal: Default::default(),
             aux_channels: config.aux_channels.clone(),
             channels: config.channels.clone(),
             monitor_groups: config.monitor_groups.clone(),
+            macros: config.macros()?,
         })
     }
 
+    /// Records changes in `journal` instead of an empty one. The journal can be shared,
+    /// so changes made through an earlier `Motu` can still be undone.
+    pub fn set_journal(&mut self, journal: Arc<Mutex<Journal>>) {
+        self.journal = journal;
+    }
+
     // runs the vector of commands
     pub fn run(&self, commands: &[MotuCommand]) -> Result<(), Box<dyn Error>> {
-        let commands: Vec<MotuCommand> = commands
-            .clone()
+        let mut batch = vec![];
+        // expanded up front, so nothing is sent if a macro is unknown
+        let commands = commands
             .iter()
-            .flat_map(|command| self.process_commands(command))
-            .filter(|command| command.osc_command().is_some())
-            .collect();
-        if commands.len() >= 10 {
-            let client = Client::new();
-
-            let payload = json_payload(&commands);
-
-            let mut headers = header::HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                "application/x-www-form-urlencoded".parse().unwrap(),
-            );
-            // Send the POST request with JSON payload
-            let response: Response = client
-                .post(&self.http_client_url)
-                .headers(headers)
-                .body(format!("json={}", payload))
-                .send()?;
-            // Check if the request was successful
-            if !response.status().is_success() {
-                println!("Request failed with status: {}", response.status());
+            .map(|command| self.process_commands(command))
+            .collect::<Result<Vec<Vec<MotuCommand>>, String>>()?
+            .concat();
+        for command in commands {
+            match command {
+                MotuCommand::Undo | MotuCommand::Redo => {
+                    self.write(&batch)?;
+                    batch.clear();
+                    self.undo_redo(&command)?;
+                }
+                _ if command.osc_command().is_some() => batch.push(command),
+                _ => (),
             }
+        }
+        self.write(&batch)
+    }
+
+    /// Reads the current values of the configured faders, sends and monitor groups
+    /// for the journal, so the first change to each doesn't wait for a datastore
+    /// request before it is sent.
+    pub fn prefetch(&self) -> Result<(), Box<dyn Error>> {
+        let addresses: Vec<String> = self
+            .process_commands(&MotuCommand::Init)?
+            .iter()
+            .filter_map(|command| command.osc_command())
+            .map(|(address, _)| address)
+            .collect();
+        let values = self.http.read(&addresses)?;
+        self.journal.lock().unwrap().remember(values);
+        Ok(())
+    }
+
+    /// Sends a batch and records it in the journal, with the previous values.
+    fn write(&self, commands: &[MotuCommand]) -> Result<(), Box<dyn Error>> {
+        if commands.is_empty() {
+            return Ok(());
+        }
+        let writes: Vec<(String, f32)> = commands
+            .iter()
+            .filter_map(|command| command.osc_command())
+            .map(|(address, value)| (address, value.parse::<f32>().unwrap_or_default()))
+            .collect();
+        let unknown = self.journal.lock().unwrap().unknown(&writes);
+        let read = if unknown.is_empty() {
+            BTreeMap::new()
         } else {
-            commands.into_iter().for_each(|command| {
-                self.send(command).unwrap();
-            });
+            self.http.read(&unknown).unwrap_or_else(|err| {
+                eprintln!("Previous values unknown, this can't be undone: {}", err);
+                BTreeMap::new()
+            })
+        };
+        self.send_batch(commands)?;
+        self.journal.lock().unwrap().record(&writes, &read)
+    }
+
+    fn undo_redo(&self, command: &MotuCommand) -> Result<(), Box<dyn Error>> {
+        let mut journal = self.journal.lock().unwrap();
+        let send = |commands: &[MotuCommand]| self.send_batch(commands);
+        let (applied, action) = match command {
+            MotuCommand::Undo => (journal.undo(send)?, "undo"),
+            _ => (journal.redo(send)?, "redo"),
+        };
+        if !applied {
+            println!("Nothing to {}", action);
         }
         Ok(())
     }
 
-    fn process_commands(&self, command: &MotuCommand) -> Vec<MotuCommand> {
+    /// Sends through the transport the policy chooses for the size of the batch.
+    fn send_batch(&self, commands: &[MotuCommand]) -> Result<(), Box<dyn Error>> {
+        let transport = match self.policy.choose(commands.len()) {
+            TransportKind::Osc => &self.osc,
+            TransportKind::Http => &self.http,
+        };
+        transport.send(commands)
+    }
+
+    fn process_commands(&self, command: &MotuCommand) -> Result<Vec<MotuCommand>, String> {
         let mut commands: Vec<MotuCommand> = vec![];
         match command {
             MotuCommand::PrintSettings => {
@@ -143,6 +222,15 @@ impl Motu {
                     commands.push(command);
                 });
             }
+            MotuCommand::Macro(name) => {
+                let macro_commands = self
+                    .macros
+                    .get(name)
+                    .ok_or_else(|| format!("Unknown macro {}", name))?;
+                for command in macro_commands {
+                    commands.extend(self.process_commands(command)?);
+                }
+            }
             MotuCommand::ToggleSend {
                 active_sends,
                 channel,
@@ -167,7 +255,7 @@ impl Motu {
                 commands.push(command.clone());
             }
         }
-        commands
+        Ok(commands)
     }
 
     pub fn print_settings(&self) -> Result<(), Box<dyn Error>> {
@@ -193,13 +281,9 @@ impl Motu {
         Ok(())
     }
     pub fn send(&self, command: MotuCommand) -> Result<(), Box<dyn Error>> {
-        let (command, value) = match command.osc_command() {
-            Some((command, value)) => (command, value),
-            None => return Err("No message found".into()),
-        };
-        let message = OscMessage::new(&command, value.parse::<f32>().unwrap_or_default());
-        let packet = OscPacket::Message(message);
-        let _ = self.client.send(packet);
-        Ok(())
+        if command.osc_command().is_none() {
+            return Err("No message found".into());
+        }
+        self.osc.send(&[command])
     }
 }
@@ -1,6 +1,7 @@
+use std::collections::HashMap;
 use std::fmt::Display;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum ChannelType {
     Aux,
     Chan,
@@ -18,6 +19,19 @@ impl Display for ChannelType {
     }
 }
 
+impl std::str::FromStr for ChannelType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "aux" => Ok(ChannelType::Aux),
+            "chan" => Ok(ChannelType::Chan),
+            "group" => Ok(ChannelType::Group),
+            _ => Err(format!("Invalid channel type: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Channel {
     channel_number: i32,
@@ -46,3 +60,54 @@ impl Display for Channel {
         write!(f, "{type}({number})", type = self.channel_type, number = self.channel_number)
     }
 }
+
+/// Names from the config's `channels`, `aux_channels` and `monitor_groups`, used
+/// wherever a channel index is accepted.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelNames {
+    names: HashMap<ChannelType, Vec<(i32, String)>>,
+}
+
+impl ChannelNames {
+    pub fn insert(&mut self, channel_type: ChannelType, index: usize, name: &str) {
+        self.names
+            .entry(channel_type)
+            .or_default()
+            .push((index as i32, name.to_string()));
+    }
+
+    /// Returns every name of every channel type.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.values().flatten().map(|(_, name)| name.as_str())
+    }
+
+    /// Resolves an index or a case-insensitive name into a channel. Names shared by
+    /// several channels of the same type are rejected as ambiguous. A `chan:`, `aux:`
+    /// or `group:` prefix addresses another type than `channel_type`, e.g. `mute(group:1)`.
+    pub fn resolve(&self, arg: &str, channel_type: ChannelType) -> Result<Channel, String> {
+        let arg = arg.trim();
+        let (channel_type, arg) = match arg.split_once(':') {
+            Some((prefix, arg)) => (prefix.trim().parse::<ChannelType>()?, arg.trim()),
+            None => (channel_type, arg),
+        };
+        if let Ok(index) = arg.parse::<i32>() {
+            return Ok(Channel::new(index, channel_type));
+        }
+        let matches: Vec<i32> = self
+            .names
+            .get(&channel_type)
+            .into_iter()
+            .flatten()
+            .filter(|(_, name)| name.eq_ignore_ascii_case(arg))
+            .map(|(index, _)| *index)
+            .collect();
+        match matches.as_slice() {
+            [index] => Ok(Channel::new(*index, channel_type)),
+            [] => Err(format!("Unknown {} name: {}", channel_type, arg)),
+            _ => Err(format!(
+                "Ambiguous {} name: {} matches {:?}",
+                channel_type, arg, matches
+            )),
+        }
+    }
+}
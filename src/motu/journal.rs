@@ -0,0 +1,373 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::MotuCommand;
+
+/// Most changes kept for undo, older ones are dropped.
+const MAX_ENTRIES: usize = 100;
+
+/// Writes to the parameters of the last change within this time belong to the same
+/// gesture, e.g. a fader sweep sent as many small batches, and are undone together.
+const GESTURE_WINDOW: Duration = Duration::from_millis(500);
+
+/// A parameter of the interface, by OSC address, and the value a command replaced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    pub address: String,
+    pub before: f32,
+    pub after: f32,
+}
+
+/// The changes applied to the interface, with what they overwrote, for undo and redo.
+///
+/// Previous values are the ones last written through the journal, parameters it
+/// hasn't seen yet are read from the datastore before the write, which costs a full
+/// datastore request the first time (see `Motu::prefetch`). Parameters whose previous
+/// value is unknown can't be undone. With a file the journal survives between runs.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Journal {
+    done: Vec<Vec<Change>>,
+    undone: Vec<Vec<Change>>,
+    #[serde(skip)]
+    values: HashMap<String, f32>,
+    #[serde(skip)]
+    file: Option<PathBuf>,
+    /// When the last change was recorded, for merging gestures.
+    #[serde(skip)]
+    recorded_at: Option<Instant>,
+}
+
+impl Journal {
+    /// Loads the journal kept in `file`, an empty one if there is none yet.
+    pub fn open(file: PathBuf) -> Result<Journal, Box<dyn Error>> {
+        let mut journal = Journal::open_read_only(&file)?;
+        journal.file = Some(file);
+        Ok(journal)
+    }
+
+    /// Loads the journal in the home directory, which undo and redo use across runs.
+    /// With `dry_run` changes aren't written back.
+    pub fn open_default(dry_run: bool) -> Result<Journal, Box<dyn Error>> {
+        match env::var_os("HOME") {
+            Some(home) => {
+                let file = PathBuf::from(home).join(".motuman_journal.json");
+                if dry_run {
+                    Journal::open_read_only(&file)
+                } else {
+                    Journal::open(file)
+                }
+            }
+            None => Ok(Journal::default()),
+        }
+    }
+
+    /// Loads the journal kept in `file` without writing changes back, for a dry run.
+    pub fn open_read_only(file: &Path) -> Result<Journal, Box<dyn Error>> {
+        match fs::read_to_string(file) {
+            Ok(json) => Ok(serde_json::from_str(&json)
+                .map_err(|err| format!("{}: {}", file.display(), err))?),
+            Err(_) => Ok(Journal::default()),
+        }
+    }
+
+    /// The addresses of `writes` without a known current value.
+    pub fn unknown(&self, writes: &[(String, f32)]) -> Vec<String> {
+        writes
+            .iter()
+            .filter(|(address, _)| !self.values.contains_key(address))
+            .map(|(address, _)| address.clone())
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Takes current values read from the interface for parameters not written yet.
+    pub fn remember(&mut self, values: BTreeMap<String, f32>) {
+        for (address, value) in values {
+            self.values.entry(address).or_insert(value);
+        }
+    }
+
+    /// Records the `writes` of a batch that was sent, `read` holds the values of
+    /// parameters the journal didn't know.
+    pub fn record(
+        &mut self,
+        writes: &[(String, f32)],
+        read: &BTreeMap<String, f32>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.record_at(writes, read, Instant::now())
+    }
+
+    fn record_at(
+        &mut self,
+        writes: &[(String, f32)],
+        read: &BTreeMap<String, f32>,
+        now: Instant,
+    ) -> Result<(), Box<dyn Error>> {
+        let gesture = self
+            .recorded_at
+            .is_some_and(|recorded_at| now.duration_since(recorded_at) < GESTURE_WINDOW);
+        self.recorded_at = Some(now);
+        if gesture && self.merge(writes) {
+            return self.save();
+        }
+
+        let mut entry: Vec<Change> = vec![];
+        for (address, after) in writes {
+            if let Some(change) = entry.iter_mut().find(|change| &change.address == address) {
+                change.after = *after;
+            } else if let Some(before) = self.values.get(address).or(read.get(address)) {
+                entry.push(Change {
+                    address: address.clone(),
+                    before: *before,
+                    after: *after,
+                });
+            }
+        }
+        for (address, after) in writes {
+            self.values.insert(address.clone(), *after);
+        }
+        entry.retain(|change| change.before != change.after);
+        if entry.is_empty() {
+            return Ok(());
+        }
+        self.done.push(entry);
+        if self.done.len() > MAX_ENTRIES {
+            self.done.remove(0);
+        }
+        self.undone.clear();
+        self.save()
+    }
+
+    /// Folds `writes` into the last change if they only touch its parameters.
+    fn merge(&mut self, writes: &[(String, f32)]) -> bool {
+        let Some(entry) = self.done.last_mut() else {
+            return false;
+        };
+        if !writes
+            .iter()
+            .all(|(address, _)| entry.iter().any(|change| &change.address == address))
+        {
+            return false;
+        }
+        for (address, after) in writes {
+            if let Some(change) = entry.iter_mut().find(|change| &change.address == address) {
+                change.after = *after;
+            }
+            self.values.insert(address.clone(), *after);
+        }
+        entry.retain(|change| change.before != change.after);
+        if entry.is_empty() {
+            self.done.pop();
+        }
+        true
+    }
+
+    /// Sends the previous values of the last change with `send`, returns `false` if
+    /// there is nothing to undo.
+    pub fn undo(
+        &mut self,
+        send: impl FnOnce(&[MotuCommand]) -> Result<(), Box<dyn Error>>,
+    ) -> Result<bool, Box<dyn Error>> {
+        let Some(entry) = self.done.last() else {
+            return Ok(false);
+        };
+        let values: Vec<(String, f32)> = entry
+            .iter()
+            .rev()
+            .map(|change| (change.address.clone(), change.before))
+            .collect();
+        self.apply(&values, send)?;
+        self.recorded_at = None;
+        let entry = self.done.pop().unwrap();
+        self.undone.push(entry);
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Sends the values of the last undone change again, returns `false` if there
+    /// is nothing to redo.
+    pub fn redo(
+        &mut self,
+        send: impl FnOnce(&[MotuCommand]) -> Result<(), Box<dyn Error>>,
+    ) -> Result<bool, Box<dyn Error>> {
+        let Some(entry) = self.undone.last() else {
+            return Ok(false);
+        };
+        let values: Vec<(String, f32)> = entry
+            .iter()
+            .map(|change| (change.address.clone(), change.after))
+            .collect();
+        self.apply(&values, send)?;
+        self.recorded_at = None;
+        let entry = self.undone.pop().unwrap();
+        self.done.push(entry);
+        self.save()?;
+        Ok(true)
+    }
+
+    fn apply(
+        &mut self,
+        values: &[(String, f32)],
+        send: impl FnOnce(&[MotuCommand]) -> Result<(), Box<dyn Error>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let commands = values
+            .iter()
+            .map(|(address, value)| command(address, *value))
+            .collect::<Result<Vec<MotuCommand>, String>>()?;
+        send(&commands)?;
+        for (address, value) in values {
+            self.values.insert(address.clone(), *value);
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(file) = &self.file {
+            fs::write(file, serde_json::to_string(self)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// The command setting the parameter at `address` to `value`.
+fn command(address: &str, value: f32) -> Result<MotuCommand, String> {
+    match MotuCommand::from_osc_address(address)? {
+        MotuCommand::Mute(channel) if value < 0.5 => Ok(MotuCommand::Unmute(channel)),
+        MotuCommand::Mute(channel) => Ok(MotuCommand::Mute(channel)),
+        mut command => {
+            command.assign(&value.to_string())?;
+            Ok(command)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(address: &str, value: f32) -> (String, f32) {
+        (address.to_string(), value)
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let fader = "/mix/chan/2/matrix/fader";
+        let mute = "/mix/group/1/matrix/mute";
+        let mut journal = Journal::default();
+        let read = BTreeMap::from([(fader.to_string(), 0.5), (mute.to_string(), 1.0)]);
+        assert_eq!(
+            journal.unknown(&[write(fader, 1.0), write(mute, 0.0)]),
+            vec![fader, mute]
+        );
+        let start = Instant::now();
+        journal
+            .record_at(&[write(fader, 1.0), write(mute, 0.0)], &read, start)
+            .unwrap();
+        journal
+            .record_at(
+                &[write(fader, 0.8), write(fader, 0.7)],
+                &BTreeMap::new(),
+                start + Duration::from_secs(1),
+            )
+            .unwrap();
+        assert!(journal.unknown(&[write(fader, 0.0)]).is_empty());
+
+        let mut sent: Vec<Vec<String>> = vec![];
+        journal
+            .undo(|commands| {
+                sent.push(commands.iter().map(MotuCommand::to_string).collect());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(journal.done[0][0].after, 1.0);
+        journal
+            .undo(|commands| {
+                sent.push(commands.iter().map(MotuCommand::to_string).collect());
+                Ok(())
+            })
+            .unwrap();
+        assert!(!journal.undo(|_| Ok(())).unwrap());
+        assert_eq!(sent, vec![vec!["vol(2)"], vec!["mute(group:1)", "vol(2)"]]);
+
+        assert!(journal.redo(|_| Ok(())).unwrap());
+        assert_eq!(journal.done.len(), 1);
+        assert_eq!(journal.undone.len(), 1);
+        journal
+            .record(&[write(fader, 0.1)], &BTreeMap::new())
+            .unwrap();
+        assert!(journal.undone.is_empty());
+    }
+
+    #[test]
+    fn test_failed_undo_is_kept() {
+        let fader = "/mix/chan/2/matrix/fader";
+        let mut journal = Journal::default();
+        journal
+            .record(
+                &[write(fader, 1.0)],
+                &BTreeMap::from([(fader.to_string(), 0.5)]),
+            )
+            .unwrap();
+        assert!(journal.undo(|_| Err("timed out".into())).is_err());
+        assert_eq!(journal.done.len(), 1);
+        assert_eq!(
+            command(fader, 0.5),
+            Ok(MotuCommand::Volume {
+                channel: crate::motu::channel::Channel::new(
+                    2,
+                    crate::motu::channel::ChannelType::Chan
+                ),
+                volume: 0.5,
+            })
+        );
+    }
+
+    #[test]
+    fn test_gesture_is_one_change() {
+        let fader = "/mix/chan/2/matrix/fader";
+        let send = "/mix/chan/2/matrix/aux/4/send";
+        let mut journal = Journal::default();
+        assert_eq!(
+            journal.unknown(&[write(fader, 1.0), write(send, 0.0), write(fader, 0.5)]),
+            vec![send, fader]
+        );
+        let read = BTreeMap::from([(fader.to_string(), 0.5), (send.to_string(), 0.3)]);
+        let start = Instant::now();
+        // a mis-hit init, then a fader sweep sent every 10 ms
+        journal
+            .record_at(&[write(fader, 1.0), write(send, 0.0)], &read, start)
+            .unwrap();
+        let start = start + Duration::from_secs(1);
+        journal
+            .record_at(&[write(fader, 0.9)], &BTreeMap::new(), start)
+            .unwrap();
+        for step in 1..200 {
+            journal
+                .record_at(
+                    &[write(fader, 0.9 - step as f32 * 0.004)],
+                    &BTreeMap::new(),
+                    start + Duration::from_millis(step * 10),
+                )
+                .unwrap();
+        }
+        assert_eq!(journal.done.len(), 2);
+        assert_eq!(journal.done[1][0].before, 1.0);
+
+        // writing another parameter starts a new change
+        journal
+            .record_at(
+                &[write(send, 0.2)],
+                &BTreeMap::new(),
+                start + Duration::from_millis(2000),
+            )
+            .unwrap();
+        assert_eq!(journal.done.len(), 3);
+    }
+}
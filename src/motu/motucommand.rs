@@ -1,7 +1,7 @@
 // motucommand from motu.rs goes here
 // Path: src/motu/motu.rs
 
-use super::channel::{Channel, ChannelType};
+use super::channel::{Channel, ChannelNames, ChannelType};
 use std::fmt::Display;
 
 const MAX_AUX_CHANNELS: usize = 8;
@@ -22,7 +22,18 @@ pub enum MotuCommand {
     },
     Mute(Channel),
     Unmute(Channel),
+    /// Pan from -1 (left) to 1 (right).
+    Pan {
+        channel: Channel,
+        pan: f32,
+    },
     Init,
+    /// A named list of commands from the config's `[macros]`.
+    Macro(String),
+    /// Reverts the last change made through the journal.
+    Undo,
+    /// Applies the last undone change again.
+    Redo,
     ToggleSend {
         channel: Channel,
         aux_channels: Vec<Channel>,
@@ -68,7 +79,13 @@ impl MotuCommand {
                 channel.channel_type(),
                 channel.channel_number()
             )),
-            MotuCommand::Init => {
+            MotuCommand::Pan { channel, pan } => Some(format!(
+                "\"mix/{}/{}/matrix/pan\":{}",
+                channel.channel_type(),
+                channel.channel_number(),
+                pan
+            )),
+            MotuCommand::Init | MotuCommand::Macro(_) | MotuCommand::Undo | MotuCommand::Redo => {
                 return None;
             }
             MotuCommand::ToggleSend {
@@ -130,7 +147,15 @@ impl MotuCommand {
                 ),
                 "0".to_string(),
             )),
-            MotuCommand::Init => {
+            MotuCommand::Pan { channel, pan } => Some((
+                format!(
+                    "/mix/{}/{}/matrix/pan",
+                    channel.channel_type(),
+                    channel.channel_number()
+                ),
+                pan.to_string(),
+            )),
+            MotuCommand::Init | MotuCommand::Macro(_) | MotuCommand::Undo | MotuCommand::Redo => {
                 return None;
             }
             MotuCommand::ToggleSend {
@@ -143,6 +168,45 @@ impl MotuCommand {
         };
         osc_command
     }
+    /// Returns the command undoing a switch-like command, `None` for commands without an opposite.
+    pub fn inverse(&self) -> Option<MotuCommand> {
+        match self {
+            MotuCommand::EnableMonitoring => Some(MotuCommand::DisableMonitoring),
+            MotuCommand::DisableMonitoring => Some(MotuCommand::EnableMonitoring),
+            MotuCommand::Mute(channel) => Some(MotuCommand::Unmute(*channel)),
+            MotuCommand::Unmute(channel) => Some(MotuCommand::Mute(*channel)),
+            _ => None,
+        }
+    }
+    /// Returns every channel the command addresses.
+    pub fn channels(&self) -> Vec<Channel> {
+        match self {
+            MotuCommand::Volume { channel, .. } => vec![*channel],
+            MotuCommand::Send {
+                channel,
+                aux_channel,
+                ..
+            } => vec![*channel, *aux_channel],
+            MotuCommand::Mute(channel)
+            | MotuCommand::Unmute(channel)
+            | MotuCommand::Pan { channel, .. } => vec![*channel],
+            MotuCommand::ToggleSend {
+                channel,
+                aux_channels,
+                ..
+            } => std::iter::once(*channel)
+                .chain(aux_channels.iter().copied())
+                .collect(),
+            _ => vec![],
+        }
+    }
+    /// Returns `true` for commands carrying a continuous value.
+    pub fn has_value(&self) -> bool {
+        matches!(
+            self,
+            MotuCommand::Volume { .. } | MotuCommand::Send { .. } | MotuCommand::Pan { .. }
+        )
+    }
     pub fn set_value(&mut self, new_value: f32) {
         match self {
             MotuCommand::Volume { channel: _, volume } => *volume = new_value,
@@ -152,6 +216,8 @@ impl MotuCommand {
                 aux_channel: _,
                 value,
             } => *value = new_value,
+            // values arrive normalized, spread them over left to right
+            MotuCommand::Pan { channel: _, pan } => *pan = new_value * 2.0 - 1.0,
             MotuCommand::ToggleSend {
                 channel: _,
                 aux_channels,
@@ -170,113 +236,343 @@ impl MotuCommand {
     }
 }
 
-impl std::str::FromStr for MotuCommand {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, String> {
-        let motu_command: MotuCommand = {
-            if s.contains("vol") {
-                let vol = s.replace("vol(", "").replace(')', "").parse::<i32>();
-                match vol {
-                    Ok(vol) => MotuCommand::Volume {
-                        channel: Channel::new(vol, ChannelType::Chan),
-                        volume: 0.66,
-                    },
-                    Err(_) => return Err("Invalid volume".to_string()),
-                }
-            } else if s.contains("send") {
-                let send = s.replace("send(", "").replace(')', "");
-                // if send contains 2 integers, it's a send command
-                let send: Vec<&str> = send.split(',').collect();
-                if send.len() == 2 {
-                    let (channel, aux_channel) = (send[0].parse::<i32>(), send[1].parse::<i32>());
-                    match (channel, aux_channel) {
-                        (Ok(channel), Ok(aux_channel)) => MotuCommand::Send {
-                            channel: Channel::new(channel, ChannelType::Chan),
-                            aux_channel: Channel::new(aux_channel, ChannelType::Aux),
-                            value: 0.33,
-                        },
-                        _ => return Err("Invalid send".to_string()),
-                    }
-                } else if send.len() < MAX_AUX_CHANNELS {
-                    let (channel, aux_channel, aux2) = (
-                        send[0].parse::<i32>(),
-                        send[1].parse::<i32>(),
-                        send[2].parse::<i32>(),
-                    );
-                    match (channel, aux_channel, aux2) {
-                        (Ok(channel), Ok(_aux_channel), Ok(_aux2)) => {
-                            MotuCommand::ToggleSend {
-                                                    channel: Channel::new(channel, ChannelType::Chan),
-                                                    aux_channels: //vec![Channel::new(aux_channel, ChannelType::Aux), Channel::new(aux2, ChannelType::Aux)],
-                                                    {
-                                                        send.iter()
-                                                            .skip(1) // Skip the first element
-                                                            .map(|s| s.parse::<i32>().map(|aux_channel| Channel::new(aux_channel, ChannelType::Aux)))
-                                                            .filter_map(Result::ok)
-                                                            .collect()
-                                                    },
-                                                    active_sends: 0,
-                                                }
-                        }
-                        _ => return Err("Invalid send".to_string()),
-                    }
-                } else {
-                    return Err("Invalid send".to_string());
-                }
-            } else if s.contains("unmute") {
-                let unmute = s.replace("unmute(", "").replace(')', "").parse::<i32>();
-                match unmute {
-                    Ok(unmute) => MotuCommand::Unmute(Channel::new(unmute, ChannelType::Chan)),
-                    Err(_) => return Err("Invalid unmute".to_string()),
-                }
-            } else if s.contains("mute") {
-                let mute = s.replace("mute(", "").replace(')', "").parse::<i32>();
-                match mute {
-                    Ok(mute) => MotuCommand::Mute(Channel::new(mute, ChannelType::Chan)),
-                    Err(_) => return Err("Invalid mute".to_string()),
+impl MotuCommand {
+    /// Parses a command of the mapping DSL, e.g. `vol(2)` or `send(OT,Space)`.
+    /// Channels can be given by index or by a name known to `names`.
+    pub fn parse(s: &str, names: &ChannelNames) -> Result<MotuCommand, String> {
+        let s = s.trim();
+        let (function, args) = match s.split_once('(') {
+            Some((function, args)) => match args.trim_end().strip_suffix(')') {
+                Some(args) => (function.trim(), args.split(',').map(str::trim).collect()),
+                None => return Err(format!("Missing closing parenthesis in {}", s)),
+            },
+            None => (s, vec![]),
+        };
+        let motu_command = match (function, args.as_slice()) {
+            ("vol", [channel]) => MotuCommand::Volume {
+                channel: names.resolve(channel, ChannelType::Chan)?,
+                volume: 0.66,
+            },
+            ("vol", _) => return Err("Invalid volume".to_string()),
+            ("send", [channel, aux_channel]) => MotuCommand::Send {
+                channel: names.resolve(channel, ChannelType::Chan)?,
+                aux_channel: names.resolve(aux_channel, ChannelType::Aux)?,
+                value: 0.33,
+            },
+            ("send", [channel, aux_channels @ ..])
+                if aux_channels.len() >= 2 && aux_channels.len() <= MAX_AUX_CHANNELS =>
+            {
+                MotuCommand::ToggleSend {
+                    channel: names.resolve(channel, ChannelType::Chan)?,
+                    aux_channels: aux_channels
+                        .iter()
+                        .map(|aux_channel| names.resolve(aux_channel, ChannelType::Aux))
+                        .collect::<Result<Vec<Channel>, String>>()?,
+                    active_sends: 0,
                 }
-            } else if s.contains("monitor") {
-                let monitor = s.replace("monitor(", "").replace(')', "");
-                match monitor.as_str() {
-                    "on" => MotuCommand::EnableMonitoring,
-                    "off" => MotuCommand::DisableMonitoring,
-                    _ => return Err("Invalid monitor".to_string()),
+            }
+            ("send", _) => return Err("Invalid send".to_string()),
+            ("mute", [channel]) => MotuCommand::Mute(names.resolve(channel, ChannelType::Chan)?),
+            ("mute", _) => return Err("Invalid mute".to_string()),
+            ("unmute", [channel]) => {
+                MotuCommand::Unmute(names.resolve(channel, ChannelType::Chan)?)
+            }
+            ("unmute", _) => return Err("Invalid unmute".to_string()),
+            ("pan", [channel]) => MotuCommand::Pan {
+                channel: names.resolve(channel, ChannelType::Chan)?,
+                pan: 0.0,
+            },
+            ("pan", _) => return Err("Invalid pan".to_string()),
+            ("monitor", ["on"]) => MotuCommand::EnableMonitoring,
+            ("monitor", ["off"]) => MotuCommand::DisableMonitoring,
+            ("monitor", _) => return Err("Invalid monitor".to_string()),
+            ("print", []) => MotuCommand::PrintSettings,
+            ("init", []) => MotuCommand::Init,
+            ("undo", []) => MotuCommand::Undo,
+            ("redo", []) => MotuCommand::Redo,
+            ("macro", [name]) if !name.is_empty() => MotuCommand::Macro(name.to_string()),
+            ("macro", _) => return Err("Invalid macro".to_string()),
+            _ => return Err("Invalid command".to_string()),
+        };
+        Ok(motu_command)
+    }
+
+    /// Parses a DSL command with a value assigned, e.g. `vol(OT)=-6dB`,
+    /// `send(2,4)=0.5` or `pan(2)=-1`. Only commands without a value, e.g. `mute(2)`,
+    /// can leave it out, mappings take theirs from the controller instead.
+    pub fn parse_assignment(s: &str, names: &ChannelNames) -> Result<MotuCommand, String> {
+        let (command, value) = match s.rsplit_once('=') {
+            Some((command, value)) => (command, Some(value.trim())),
+            None => (s, None),
+        };
+        let mut motu_command = MotuCommand::parse(command, names)?;
+        match value {
+            Some(value) => motu_command.assign(value)?,
+            None if motu_command.has_value() => {
+                let example = match motu_command {
+                    MotuCommand::Pan { .. } => "0",
+                    _ => "-6dB",
+                };
+                return Err(format!(
+                    "{} needs a value, e.g. {}={}",
+                    motu_command, motu_command, example
+                ));
+            }
+            None => (),
+        }
+        Ok(motu_command)
+    }
+
+    /// Parses commands separated by `;`, each as in `parse_assignment`.
+    pub fn parse_list(s: &str, names: &ChannelNames) -> Result<Vec<MotuCommand>, String> {
+        s.split(';')
+            .filter(|command| !command.trim().is_empty())
+            .map(|command| MotuCommand::parse_assignment(command, names))
+            .collect()
+    }
+
+    /// Sets the value of a command from text, levels of faders and sends can be
+    /// given in dB (`-6dB`, `-inf`) or as gain, pans from -1 (left) to 1 (right).
+    pub fn assign(&mut self, value: &str) -> Result<(), String> {
+        match self {
+            MotuCommand::Volume { volume: level, .. } | MotuCommand::Send { value: level, .. } => {
+                *level = parse_level(value)?
+            }
+            MotuCommand::Pan { pan, .. } => {
+                *pan = match value.parse::<f32>() {
+                    Ok(pan) if (-1.0..=1.0).contains(&pan) => pan,
+                    _ => return Err(format!("Invalid pan {}, expected -1 to 1", value)),
                 }
-            } else if s.contains("print") {
-                MotuCommand::PrintSettings
-            } else if s.contains("init") {
-                MotuCommand::Init
-            } else {
-                return Err("Invalid command".to_string());
             }
+            _ => return Err(format!("{} takes no value", self)),
+        }
+        Ok(())
+    }
+
+    /// Turns an OSC address of the interface, e.g. `/mix/chan/42/matrix/aux/0/send`,
+    /// back into the command sending to it.
+    pub fn from_osc_address(address: &str) -> Result<MotuCommand, String> {
+        let parts: Vec<&str> = address.trim_start_matches('/').split('/').collect();
+        let (channel_type, number, parameter) = match parts.as_slice() {
+            ["mix", channel_type, number, "matrix", parameter @ ..] => {
+                (channel_type, number, parameter)
+            }
+            _ => return Err(format!("Unsupported address {}", address)),
+        };
+        let channel = Channel::new(
+            number
+                .parse()
+                .map_err(|_| format!("Invalid channel number in {}", address))?,
+            channel_type.parse()?,
+        );
+        let motu_command = match parameter {
+            ["fader"] => MotuCommand::Volume {
+                channel,
+                volume: 0.66,
+            },
+            ["aux", aux_channel, "send"] => MotuCommand::Send {
+                channel,
+                aux_channel: Channel::new(
+                    aux_channel
+                        .parse()
+                        .map_err(|_| format!("Invalid aux channel number in {}", address))?,
+                    ChannelType::Aux,
+                ),
+                value: 0.33,
+            },
+            ["mute"] => MotuCommand::Mute(channel),
+            ["pan"] => MotuCommand::Pan { channel, pan: 0.0 },
+            _ => return Err(format!("Unsupported address {}", address)),
         };
         Ok(motu_command)
     }
 }
 
+/// Highest fader and send gain of the interface, +12 dB.
+const MAX_GAIN: f32 = 4.0;
+
+/// Parses a level as dB (`-6dB`, `-inf`) or as linear gain (`0.5`), the gain the
+/// interface expects is returned.
+pub fn parse_level(s: &str) -> Result<f32, String> {
+    let s = s.trim();
+    let invalid = || format!("Invalid level {}, expected e.g. -6dB or 0.5", s);
+    let lower = s.to_lowercase();
+    let gain = match lower.strip_suffix("db").map(str::trim) {
+        Some("-inf") => 0.0,
+        Some(db) => 10f32.powf(db.parse::<f32>().map_err(|_| invalid())? / 20.0),
+        None if lower == "-inf" => 0.0,
+        None => s.parse::<f32>().map_err(|_| invalid())?,
+    };
+    if !(0.0..=MAX_GAIN).contains(&gain) {
+        return Err(format!("Level {} is out of range, the maximum is +12dB", s));
+    }
+    Ok(gain)
+}
+
+/// Writes a channel argument of the DSL, prefixed with its type if that isn't `default`.
+fn channel_arg(channel: &Channel, default: ChannelType) -> String {
+    if *channel.channel_type() == default {
+        channel.channel_number().to_string()
+    } else {
+        format!("{}:{}", channel.channel_type(), channel.channel_number())
+    }
+}
+
+impl std::str::FromStr for MotuCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        MotuCommand::parse(s, &ChannelNames::default())
+    }
+}
+
+/// Writes the command in the mapping DSL, so it parses back into the same command.
 impl Display for MotuCommand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let chan = |channel: &Channel| channel_arg(channel, ChannelType::Chan);
+        let aux = |channel: &Channel| channel_arg(channel, ChannelType::Aux);
         match self {
-            MotuCommand::EnableMonitoring => write!(f, "EnableMonitoring"),
-            MotuCommand::DisableMonitoring => write!(f, "DisableMonitoring"),
-            MotuCommand::PrintSettings => write!(f, "PrintSettings"),
-            MotuCommand::Volume { channel, volume } => {
-                write!(f, "Volume: {} {}", channel, volume)
-            }
+            MotuCommand::EnableMonitoring => write!(f, "monitor(on)"),
+            MotuCommand::DisableMonitoring => write!(f, "monitor(off)"),
+            MotuCommand::PrintSettings => write!(f, "print"),
+            MotuCommand::Volume { channel, .. } => write!(f, "vol({})", chan(channel)),
             MotuCommand::Send {
                 channel,
                 aux_channel,
-                value,
-            } => write!(f, "Send: {} {} {}", channel, aux_channel, value),
-            MotuCommand::Mute(channel) => write!(f, "Mute: {}", channel),
-            MotuCommand::Unmute(channel) => write!(f, "Unmute: {}", channel),
-            MotuCommand::Init => write!(f, "Init"),
+                ..
+            } => write!(f, "send({},{})", chan(channel), aux(aux_channel)),
+            MotuCommand::Mute(channel) => write!(f, "mute({})", chan(channel)),
+            MotuCommand::Unmute(channel) => write!(f, "unmute({})", chan(channel)),
+            MotuCommand::Pan { channel, .. } => write!(f, "pan({})", chan(channel)),
+            MotuCommand::Init => write!(f, "init"),
+            MotuCommand::Undo => write!(f, "undo"),
+            MotuCommand::Redo => write!(f, "redo"),
+            MotuCommand::Macro(name) => write!(f, "macro({})", name),
             MotuCommand::ToggleSend {
                 channel,
                 aux_channels,
-                active_sends,
-            } => write!(f, "Send: {} {:#?} {}", channel, aux_channels, active_sends),
+                ..
+            } => {
+                write!(f, "send({}", chan(channel))?;
+                for aux_channel in aux_channels {
+                    write!(f, ",{}", aux(aux_channel))?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> ChannelNames {
+        let mut names = ChannelNames::default();
+        names.insert(ChannelType::Chan, 2, "OT");
+        names.insert(ChannelType::Chan, 32, "Syntakt");
+        names.insert(ChannelType::Aux, 4, "H90");
+        names.insert(ChannelType::Aux, 6, "Space");
+        names
+    }
+
+    #[test]
+    fn test_parse_names() {
+        assert_eq!(
+            MotuCommand::parse("send(syntakt, H90)", &names()),
+            Ok(MotuCommand::Send {
+                channel: Channel::new(32, ChannelType::Chan),
+                aux_channel: Channel::new(4, ChannelType::Aux),
+                value: 0.33,
+            })
+        );
+        assert_eq!(
+            MotuCommand::parse("vol(OT)", &names()),
+            "vol(2)".parse::<MotuCommand>()
+        );
+        assert!(MotuCommand::parse("vol(H90)", &names()).is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for dsl in [
+            "vol(2)",
+            "send(31,4)",
+            "send(31,6,2,0)",
+            "mute(0)",
+            "unmute(2)",
+            "mute(group:1)",
+            "vol(aux:3)",
+            "pan(4)",
+            "monitor(on)",
+            "monitor(off)",
+            "print",
+            "init",
+            "undo",
+            "redo",
+            "macro(vocal_check)",
+        ] {
+            let command = dsl.parse::<MotuCommand>().unwrap();
+            assert_eq!(command.to_string(), dsl);
+        }
+    }
+
+    #[test]
+    fn test_from_osc_address() {
+        for (address, dsl) in [
+            ("/mix/chan/42/matrix/fader", "vol(42)"),
+            ("/mix/chan/42/matrix/aux/0/send", "send(42,0)"),
+            ("/mix/group/1/matrix/mute", "mute(group:1)"),
+            ("/mix/chan/3/matrix/pan", "pan(3)"),
+        ] {
+            assert_eq!(
+                MotuCommand::from_osc_address(address).map(|command| command.to_string()),
+                Ok(dsl.to_string())
+            );
+        }
+        assert!(MotuCommand::from_osc_address("/mix/chan/3/matrix/solo").is_err());
+        assert!(MotuCommand::from_osc_address("/ext/obank/0/ch/0/name").is_err());
+    }
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("0dB"), Ok(1.0));
+        assert_eq!(parse_level("-inf"), Ok(0.0));
+        assert_eq!(parse_level("0.5"), Ok(0.5));
+        assert!((parse_level("-6 dB").unwrap() - 0.501).abs() < 0.001);
+        assert!(parse_level("+12dB").is_ok());
+        assert_eq!(parse_level("4"), Ok(4.0));
+        assert!(parse_level("13dB").is_err());
+        assert!(parse_level("-0.5").is_err());
+        assert!(parse_level("loud").is_err());
+    }
+
+    #[test]
+    fn test_parse_assignment() {
+        assert_eq!(
+            MotuCommand::parse_assignment("send(OT,Space)=0dB", &names()),
+            Ok(MotuCommand::Send {
+                channel: Channel::new(2, ChannelType::Chan),
+                aux_channel: Channel::new(6, ChannelType::Aux),
+                value: 1.0,
+            })
+        );
+        assert_eq!(
+            MotuCommand::parse_assignment("pan(2) = -1", &names()),
+            Ok(MotuCommand::Pan {
+                channel: Channel::new(2, ChannelType::Chan),
+                pan: -1.0,
+            })
+        );
+        assert!(MotuCommand::parse_assignment("pan(2)=2", &names()).is_err());
+        assert!(MotuCommand::parse_assignment("mute(2)=1", &names()).is_err());
+        assert_eq!(
+            MotuCommand::parse_assignment("vol(OT)", &names()),
+            Err(String::from("vol(2) needs a value, e.g. vol(2)=-6dB"))
+        );
+        assert!(MotuCommand::parse_assignment("send(2,4)", &names()).is_err());
+        assert!(MotuCommand::parse_assignment("pan(2)", &names()).is_err());
+        assert!(MotuCommand::parse_assignment("mute(2)", &names()).is_ok());
+        assert!(MotuCommand::parse_assignment("send(2,4,6)", &names()).is_ok());
+    }
+}
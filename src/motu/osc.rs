@@ -1,13 +1,26 @@
 extern crate rosc;
 
-// use rosc::OscMessage;
+use rosc::OscMessage;
 use rosc::OscPacket;
+use rosc::{OscBundle, OscTime};
 // use rosc::OscType;
 // use rosc::OscColor;
 
 // use std::net::{UdpSocket, ToSocketAddrs};
 use std::net::UdpSocket;
 
+/// Largest UDP payload that fits an Ethernet frame without fragmenting.
+pub const MAX_PACKET_SIZE: usize = 1472;
+
+/// `#bundle` and the timetag.
+const BUNDLE_HEADER_SIZE: usize = 16;
+
+/// The timetag meaning "immediately".
+const IMMEDIATELY: OscTime = OscTime {
+    seconds: 0,
+    fractional: 1,
+};
+
 pub struct OscClient {
     socket: UdpSocket,
     server_address: String,
@@ -30,4 +43,115 @@ impl OscClient {
             .map_err(|e| format!("{}", e))?;
         Ok(())
     }
+
+    /// Sends the messages as bundles to be applied immediately. Batches too large for
+    /// one packet are split.
+    pub fn send_bundled(&self, messages: Vec<OscMessage>) -> Result<(), String> {
+        // a wall-clock timetag would be scheduled by the interface's own clock
+        for bundle in bundles(messages, IMMEDIATELY, MAX_PACKET_SIZE)? {
+            self.send(bundle)?;
+        }
+        Ok(())
+    }
+}
+
+/// Groups messages into bundles whose encoding stays within `max_size` bytes. A
+/// message too large on its own gets a bundle of its own.
+pub fn bundles(
+    messages: Vec<OscMessage>,
+    timetag: OscTime,
+    max_size: usize,
+) -> Result<Vec<OscPacket>, String> {
+    let mut bundles = vec![];
+    let mut content = vec![];
+    let mut size = BUNDLE_HEADER_SIZE;
+    for message in messages {
+        let message = OscPacket::Message(message);
+        // every element is prefixed with its size
+        let message_size = 4 + rosc::encoder::encode(&message)
+            .map_err(|e| format!("{}", e))?
+            .len();
+        if !content.is_empty() && size + message_size > max_size {
+            bundles.push(OscPacket::Bundle(OscBundle {
+                timetag,
+                content: std::mem::take(&mut content),
+            }));
+            size = BUNDLE_HEADER_SIZE;
+        }
+        content.push(message);
+        size += message_size;
+    }
+    if !content.is_empty() {
+        bundles.push(OscPacket::Bundle(OscBundle { timetag, content }));
+    }
+    Ok(bundles)
+}
+
+/// Receives OSC from tablets or other software.
+pub struct OscServer {
+    socket: UdpSocket,
+}
+
+impl OscServer {
+    pub fn bind(port: u16) -> Result<Self, String> {
+        let socket = UdpSocket::bind(("0.0.0.0", port)).map_err(|e| format!("{}", e))?;
+        Ok(Self { socket })
+    }
+
+    /// Waits for the next packet and returns its messages, bundles are flattened.
+    pub fn recv(&self) -> Result<Vec<OscMessage>, String> {
+        let mut buffer = [0u8; rosc::decoder::MTU];
+        let size = self
+            .socket
+            .recv(&mut buffer)
+            .map_err(|e| format!("{}", e))?;
+        let (_, packet) =
+            rosc::decoder::decode_udp(&buffer[..size]).map_err(|e| format!("{}", e))?;
+        let mut messages = vec![];
+        flatten(packet, &mut messages);
+        Ok(messages)
+    }
+}
+
+fn flatten(packet: OscPacket, messages: &mut Vec<OscMessage>) {
+    match packet {
+        OscPacket::Message(message) => messages.push(message),
+        OscPacket::Bundle(bundle) => {
+            for packet in bundle.content {
+                flatten(packet, messages);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rosc::OscType;
+
+    fn message(index: usize) -> OscMessage {
+        OscMessage {
+            addr: format!("/mix/chan/{}/matrix/fader", index),
+            args: vec![OscType::Float(0.5)],
+        }
+    }
+
+    #[test]
+    fn test_bundles_split_at_max_size() {
+        let messages: Vec<OscMessage> = (0..100).map(message).collect();
+        let packets = bundles(messages, IMMEDIATELY, MAX_PACKET_SIZE).unwrap();
+        assert!(packets.len() > 1);
+
+        let mut indices = vec![];
+        for bundle in &packets {
+            assert!(rosc::encoder::encode(bundle).unwrap().len() <= MAX_PACKET_SIZE);
+            let mut messages = vec![];
+            flatten(bundle.clone(), &mut messages);
+            indices.extend(messages.into_iter().map(|message| message.addr));
+        }
+        let expected: Vec<String> = (0..100).map(|index| message(index).addr).collect();
+        assert_eq!(indices, expected);
+
+        assert_eq!(bundles(vec![message(1)], IMMEDIATELY, 8).unwrap().len(), 1);
+    }
 }
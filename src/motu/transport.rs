@@ -0,0 +1,342 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use reqwest::{blocking::Client, header, StatusCode};
+use rosc::OscMessage;
+use serde::{Deserialize, Serialize};
+
+use super::osc::OscClient;
+use super::{json_payload, MotuCommand, OscSender};
+
+/// A way of getting commands to the interface.
+pub trait Transport: Send {
+    /// Sends a batch of commands, all of them have an `osc_command`.
+    fn send(&self, commands: &[MotuCommand]) -> Result<(), Box<dyn Error>>;
+
+    /// Reads the current values of the parameters at the OSC `addresses`, the ones
+    /// the transport can't read are left out.
+    fn read(&self, _addresses: &[String]) -> Result<BTreeMap<String, f32>, Box<dyn Error>> {
+        Ok(BTreeMap::new())
+    }
+}
+
+/// OSC over UDP, batches go out as bundles.
+pub struct OscTransport {
+    client: OscClient,
+}
+
+impl OscTransport {
+    pub fn new(server_address: &str) -> Result<OscTransport, String> {
+        Ok(OscTransport {
+            client: OscClient::new(server_address)?,
+        })
+    }
+}
+
+impl Transport for OscTransport {
+    fn send(&self, commands: &[MotuCommand]) -> Result<(), Box<dyn Error>> {
+        let messages = commands
+            .iter()
+            .filter_map(|command| command.osc_command())
+            .map(|(address, value)| {
+                OscMessage::new(&address, value.parse::<f32>().unwrap_or_default())
+            })
+            .collect();
+        self.client.send_bundled(messages)?;
+        Ok(())
+    }
+}
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_ATTEMPTS: u32 = 3;
+const FIRST_BACKOFF: Duration = Duration::from_millis(100);
+
+/// A single POST to the HTTP datastore per batch.
+///
+/// The client is built once and keeps its connection alive between batches. Writes
+/// to the datastore set absolute values, so failed attempts are retried.
+pub struct HttpTransport {
+    url: String,
+    client: Client,
+}
+
+impl HttpTransport {
+    pub fn new(ip: &str) -> Result<HttpTransport, Box<dyn Error>> {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded".parse().unwrap(),
+        );
+        let client = Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .default_headers(headers)
+            .build()?;
+        Ok(HttpTransport {
+            url: format!("http://{}/datastore", ip),
+            client,
+        })
+    }
+
+    fn post(&self, body: &str) -> Result<(), Attempt> {
+        let response = self
+            .client
+            .post(&self.url)
+            .body(body.to_string())
+            .send()
+            .map_err(|err| Attempt::Failed(err.into()))?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            Err(Attempt::Failed(
+                format!("{} returned {}", self.url, status).into(),
+            ))
+        } else {
+            Err(Attempt::Rejected(
+                format!("{} returned {}", self.url, status).into(),
+            ))
+        }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn send(&self, commands: &[MotuCommand]) -> Result<(), Box<dyn Error>> {
+        let body = format!("json={}", json_payload(commands));
+        retry(MAX_ATTEMPTS, FIRST_BACKOFF, || self.post(&body))
+    }
+
+    /// Reads the whole datastore in one request, its keys are the OSC addresses
+    /// without the leading `/`.
+    fn read(&self, addresses: &[String]) -> Result<BTreeMap<String, f32>, Box<dyn Error>> {
+        let datastore: serde_json::Value = self
+            .client
+            .get(&self.url)
+            .send()?
+            .error_for_status()?
+            .json()?;
+        Ok(addresses
+            .iter()
+            .filter_map(|address| {
+                let value = datastore.get(address.trim_start_matches('/'))?.as_f64()?;
+                Some((address.clone(), value as f32))
+            })
+            .collect())
+    }
+}
+
+/// Why an attempt failed: worth another try, or rejected for good.
+enum Attempt {
+    Failed(Box<dyn Error>),
+    Rejected(Box<dyn Error>),
+}
+
+/// Runs `attempt` up to `attempts` times, doubling the wait after each failure.
+fn retry(
+    attempts: u32,
+    backoff: Duration,
+    mut attempt: impl FnMut() -> Result<(), Attempt>,
+) -> Result<(), Box<dyn Error>> {
+    let mut backoff = backoff;
+    for remaining in (0..attempts).rev() {
+        match attempt() {
+            Ok(()) => return Ok(()),
+            Err(Attempt::Failed(err)) if remaining == 0 => {
+                return Err(format!("{} (gave up after {} attempts)", err, attempts).into())
+            }
+            Err(Attempt::Failed(_)) => {
+                thread::sleep(backoff);
+                backoff *= 2;
+            }
+            Err(Attempt::Rejected(err)) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// Keeps every batch instead of sending it, for tests.
+#[derive(Clone, Default)]
+pub struct RecordingTransport {
+    sent: Arc<Mutex<Vec<Vec<MotuCommand>>>>,
+}
+
+impl RecordingTransport {
+    /// The batches sent so far, the recorder can be cloned before handing it over.
+    pub fn sent(&self) -> Vec<Vec<MotuCommand>> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn send(&self, commands: &[MotuCommand]) -> Result<(), Box<dyn Error>> {
+        self.sent.lock().unwrap().push(commands.to_vec());
+        Ok(())
+    }
+}
+
+/// Prints what would be sent instead of sending it, for `--dry-run`.
+pub struct PrintingTransport {
+    kind: TransportKind,
+    target: String,
+}
+
+impl PrintingTransport {
+    pub fn osc(server_address: &str) -> PrintingTransport {
+        PrintingTransport {
+            kind: TransportKind::Osc,
+            target: server_address.to_string(),
+        }
+    }
+
+    pub fn http(ip: &str) -> PrintingTransport {
+        PrintingTransport {
+            kind: TransportKind::Http,
+            target: format!("http://{}/datastore", ip),
+        }
+    }
+
+    /// One line per OSC message, or the body of the single HTTP request.
+    fn lines(&self, commands: &[MotuCommand]) -> Vec<String> {
+        match self.kind {
+            TransportKind::Osc => commands
+                .iter()
+                .filter_map(|command| command.osc_command())
+                .map(|(address, value)| {
+                    format!(
+                        "OSC {} {} {}",
+                        self.target,
+                        address,
+                        value.parse::<f32>().unwrap_or_default()
+                    )
+                })
+                .collect(),
+            TransportKind::Http => vec![format!(
+                "POST {} json={}",
+                self.target,
+                json_payload(commands)
+            )],
+        }
+    }
+}
+
+impl Transport for PrintingTransport {
+    fn send(&self, commands: &[MotuCommand]) -> Result<(), Box<dyn Error>> {
+        for line in self.lines(commands) {
+            println!("{}", line);
+        }
+        Ok(())
+    }
+}
+
+/// Which transport `Motu::run` uses for a batch, set as `transport` in `[network]`:
+/// `"osc"`, `"http"` or `{ threshold = 10 }`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportPolicy {
+    Osc,
+    Http,
+    /// HTTP for batches of at least this many commands, OSC for smaller ones.
+    Threshold(usize),
+}
+
+impl Default for TransportPolicy {
+    fn default() -> Self {
+        TransportPolicy::Threshold(DEFAULT_THRESHOLD)
+    }
+}
+
+const DEFAULT_THRESHOLD: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransportKind {
+    Osc,
+    Http,
+}
+
+impl TransportPolicy {
+    pub fn choose(&self, batch_size: usize) -> TransportKind {
+        match self {
+            TransportPolicy::Osc => TransportKind::Osc,
+            TransportPolicy::Http => TransportKind::Http,
+            TransportPolicy::Threshold(size) if batch_size >= *size => TransportKind::Http,
+            TransportPolicy::Threshold(_) => TransportKind::Osc,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motu::channel::{Channel, ChannelType};
+
+    #[test]
+    fn test_choose() {
+        assert_eq!(TransportPolicy::default().choose(9), TransportKind::Osc);
+        assert_eq!(TransportPolicy::default().choose(10), TransportKind::Http);
+        assert_eq!(TransportPolicy::Osc.choose(40), TransportKind::Osc);
+        assert_eq!(TransportPolicy::Http.choose(1), TransportKind::Http);
+    }
+
+    #[test]
+    fn test_printing_transport() {
+        let commands = vec![
+            MotuCommand::Mute(Channel::new(1, ChannelType::Group)),
+            MotuCommand::Volume {
+                channel: Channel::new(2, ChannelType::Chan),
+                volume: 0.5,
+            },
+        ];
+        assert_eq!(
+            PrintingTransport::osc("127.0.0.1:8000").lines(&commands),
+            vec![
+                "OSC 127.0.0.1:8000 /mix/group/1/matrix/mute 1",
+                "OSC 127.0.0.1:8000 /mix/chan/2/matrix/fader 0.5",
+            ]
+        );
+        assert_eq!(
+            PrintingTransport::http("127.0.0.1").lines(&commands),
+            vec![format!(
+                "POST http://127.0.0.1/datastore json={}",
+                json_payload(&commands)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_retry() {
+        let mut calls = 0;
+        let result = retry(3, Duration::ZERO, || {
+            calls += 1;
+            if calls < 3 {
+                Err(Attempt::Failed("timed out".into()))
+            } else {
+                Ok(())
+            }
+        });
+        assert!(result.is_ok());
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let result = retry(3, Duration::ZERO, || {
+            calls += 1;
+            Err(Attempt::Failed("timed out".into()))
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "timed out (gave up after 3 attempts)"
+        );
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let result = retry(3, Duration::ZERO, || {
+            calls += 1;
+            Err(Attempt::Rejected("400 Bad Request".into()))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::sync::Arc;
+
+use rosc::{OscMessage, OscType};
+
+use crate::config::{Config, Reload};
+use crate::midi::mapping::{MappingOptions, MidiMapping};
+use crate::midi::midicommand::now_millis;
+use crate::midi::queue::SendQueue;
+use crate::midi::throttle::{Throttle, ThrottlePolicy};
+use crate::motu::channel::ChannelNames;
+use crate::motu::MotuCommand;
+
+/// Prefix of the addresses the bridge answers to.
+pub const ADDRESS_PREFIX: &str = "/motuman/";
+
+/// Turns incoming OSC into commands for the interface.
+///
+/// Addresses are either keys of the config's `[osc_mapping]`, e.g. `/motuman/fader1`,
+/// or commands of the DSL written as a path, e.g. `/motuman/vol/OT`,
+/// `/motuman/send/OT/Space` or `/motuman/macro/vocal_check`. The first argument is
+/// the value, from 0 to 1, and goes through the same options and throttling as MIDI.
+pub struct OscBridge {
+    mappings: BTreeMap<String, MidiMapping>,
+    names: ChannelNames,
+    throttle: ThrottlePolicy,
+    routes: HashMap<String, Route>,
+    queue: Arc<SendQueue>,
+}
+
+impl OscBridge {
+    pub fn new(config: &Config, queue: Arc<SendQueue>) -> OscBridge {
+        OscBridge {
+            mappings: config.osc_mapping.clone(),
+            names: config.channel_names(),
+            throttle: throttle(config),
+            routes: HashMap::new(),
+            queue,
+        }
+    }
+
+    /// Queues the commands for `message`, they are sent through the same `SendQueue`
+    /// as MIDI.
+    pub fn handle(&mut self, message: &OscMessage) -> Result<(), Box<dyn Error>> {
+        let value = message.args.first().map(osc_value).transpose()?;
+        let commands = self.commands(&message.addr, value, now_millis()?)?;
+        if !commands.is_empty() {
+            self.queue.push(commands);
+        }
+        Ok(())
+    }
+
+    /// Returns the commands for a message to `address` carrying `value`.
+    fn commands(
+        &mut self,
+        address: &str,
+        value: Option<f32>,
+        timestamp: u64,
+    ) -> Result<Vec<MotuCommand>, String> {
+        if !self.routes.contains_key(address) {
+            let route = self.route(address)?;
+            self.routes.insert(address.to_string(), route);
+        }
+        let route = self.routes.get_mut(address).unwrap();
+        Ok(route.process(value, timestamp))
+    }
+
+    fn route(&self, address: &str) -> Result<Route, String> {
+        let path = address
+            .strip_prefix(ADDRESS_PREFIX)
+            .ok_or_else(|| format!("Address {} is not below {}", address, ADDRESS_PREFIX))?;
+        let mapping = match self.mappings.get(path) {
+            Some(mapping) => mapping.clone(),
+            None => {
+                let (function, args) = path.split_once('/').unwrap_or((path, ""));
+                let dsl = if args.is_empty() {
+                    function.to_string()
+                } else {
+                    format!("{}({})", function, args.replace('/', ","))
+                };
+                MidiMapping::from(
+                    MotuCommand::parse(&dsl, &self.names)
+                        .map_err(|err| format!("{}: {}", err, address))?,
+                )
+            }
+        };
+        let throttle = mapping.options.throttle.as_ref().unwrap_or(&self.throttle);
+        Ok(Route {
+            throttle: throttle.build(),
+            command: mapping.command,
+            options: mapping.options,
+        })
+    }
+}
+
+impl Reload for OscBridge {
+    type Prepared = OscBridge;
+
+    fn prepare(&self, config: &Config) -> Result<OscBridge, Box<dyn Error>> {
+        Ok(OscBridge::new(config, self.queue.clone()))
+    }
+
+    /// Takes the new mappings and names, routes are rebuilt as messages arrive.
+    fn apply(&mut self, prepared: OscBridge) {
+        *self = prepared;
+    }
+}
+
+fn throttle(config: &Config) -> ThrottlePolicy {
+    config
+        .osc_server
+        .as_ref()
+        .map(|osc_server| osc_server.throttle.clone())
+        .unwrap_or_default()
+}
+
+/// The command an address resolved to, with the state of its throttle.
+struct Route {
+    command: MotuCommand,
+    options: MappingOptions,
+    throttle: Box<dyn Throttle>,
+}
+
+impl Route {
+    fn process(&mut self, value: Option<f32>, timestamp: u64) -> Vec<MotuCommand> {
+        if self.command.has_value() {
+            let x = value.unwrap_or(1.0).clamp(0.0, 1.0);
+            // throttles work on the MIDI scale
+            if !self.throttle.allow(x * 127.0, timestamp) {
+                return vec![];
+            }
+            self.command.set_value(self.options.apply(x));
+            return vec![self.command.clone()];
+        }
+        match value {
+            // switches are released with 0, e.g. a toggle button sending 1 and 0
+            Some(0.0) => self.command.inverse().into_iter().collect(),
+            _ => {
+                // cycles the sends of a ToggleSend
+                self.command.set_value(1.0);
+                vec![self.command.clone()]
+            }
+        }
+    }
+}
+
+fn osc_value(arg: &OscType) -> Result<f32, String> {
+    match arg {
+        OscType::Float(value) => Ok(*value),
+        OscType::Double(value) => Ok(*value as f32),
+        OscType::Int(value) => Ok(*value as f32),
+        OscType::Long(value) => Ok(*value as f32),
+        OscType::Bool(value) => Ok(if *value { 1.0 } else { 0.0 }),
+        _ => Err(format!("Unsupported OSC argument {:?}", arg)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bridge() -> OscBridge {
+        let config = Config::parse(
+            r#"
+            [network]
+            ip_address = "127.0.0.1:8000"
+            [channels]
+            2 = "OT"
+            [aux_channels]
+            6 = "Space"
+            [monitor_groups]
+            [midi_mapping_cc]
+            [midi_mapping_note_on]
+            [midi_mapping_note_off]
+            [osc_server]
+            port = 9000
+            throttle = "none"
+            [osc_mapping]
+            fader1 = { command = "vol(OT)", range = [0.0, 0.5], curve = "linear" }
+            "#,
+        )
+        .unwrap();
+        let queue = crate::midi::bridge::send_queue(&config, true, &Default::default()).unwrap();
+        OscBridge::new(&config, Arc::new(queue))
+    }
+
+    #[test]
+    fn test_friendly_addresses() {
+        let mut bridge = bridge();
+        assert_eq!(
+            bridge
+                .commands("/motuman/send/OT/Space", Some(1.0), 0)
+                .unwrap()
+                .iter()
+                .map(MotuCommand::to_string)
+                .collect::<Vec<String>>(),
+            vec!["send(2,6)"]
+        );
+        assert_eq!(
+            bridge.commands("/motuman/mute/group:1", Some(0.0), 0),
+            "unmute(group:1)".parse().map(|command| vec![command])
+        );
+        assert!(bridge.commands("/motuman/vol/Nope", Some(1.0), 0).is_err());
+        assert!(bridge
+            .commands("/mix/chan/2/matrix/fader", Some(1.0), 0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_mapping_options_apply() {
+        let mut bridge = bridge();
+        assert_eq!(
+            bridge.commands("/motuman/fader1", Some(1.0), 0),
+            Ok(vec![MotuCommand::Volume {
+                channel: crate::motu::channel::Channel::new(
+                    2,
+                    crate::motu::channel::ChannelType::Chan
+                ),
+                volume: 0.5,
+            }])
+        );
+    }
+}
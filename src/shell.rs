@@ -0,0 +1,203 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::path::PathBuf;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use crate::motu::channel::ChannelNames;
+use crate::motu::{Motu, MotuCommand};
+
+const FUNCTIONS: [&str; 13] = [
+    "vol", "send", "pan", "mute", "unmute", "monitor", "print", "init", "macro", "undo", "redo",
+    "help", "quit",
+];
+
+const HELP: &str = "Commands of the mapping DSL, several separated by ';':
+  vol(OT)=-6dB  send(OT,Space)=0.5  pan(OT)=-1  mute(group:1)  unmute(2)
+  monitor(on)  monitor(off)  print  init  macro(name)  undo  redo
+vol, send and pan need a value. Channels are indices or names from the config,
+tab completes both.
+help shows this, quit or Ctrl-D leaves the shell.";
+
+/// Reads DSL commands from an interactive prompt and runs them on `motu`, which
+/// stays connected for the whole session.
+pub fn shell(motu: &Motu, names: &ChannelNames) -> Result<(), Box<dyn Error>> {
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ShellHelper::new(names)));
+    let history = history_file();
+    if let Some(history) = &history {
+        // there is no history on the first run
+        let _ = editor.load_history(history);
+    }
+
+    println!("Type help for the commands, quit to leave.");
+    loop {
+        let line = match editor.readline("motu> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+        match line {
+            "quit" | "exit" => break,
+            "help" => println!("{}", HELP),
+            _ => match parse_line(line, names) {
+                Ok(commands) => {
+                    if let Err(err) = motu.run(&commands) {
+                        println!("Error: {}", err);
+                    }
+                }
+                Err(err) => println!("{}", err),
+            },
+        }
+    }
+
+    if let Some(history) = &history {
+        editor.save_history(history)?;
+    }
+    Ok(())
+}
+
+/// Parses a line of commands, nothing of it is run if one of them is invalid.
+fn parse_line(line: &str, names: &ChannelNames) -> Result<Vec<MotuCommand>, String> {
+    MotuCommand::parse_list(line, names).map_err(|err| format!("{}: {}", err, line))
+}
+
+fn history_file() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".motuman_history"))
+}
+
+struct ShellHelper {
+    channel_names: Vec<String>,
+}
+
+impl ShellHelper {
+    fn new(names: &ChannelNames) -> ShellHelper {
+        let mut channel_names: Vec<String> = names.names().map(String::from).collect();
+        channel_names.sort();
+        channel_names.dedup();
+        ShellHelper { channel_names }
+    }
+
+    /// Completes the word before `pos`, function names at the start of a command
+    /// and channel names inside the parentheses.
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let before = &line[..pos];
+        let start = before
+            .rfind(|c: char| "(,;:=".contains(c))
+            .map_or(0, |index| index + 1);
+        // names can contain spaces, only leading ones are skipped
+        let start = start + (before[start..].len() - before[start..].trim_start().len());
+        let word = before[start..].to_lowercase();
+        let in_arguments = before[..start].trim_end().ends_with(['(', ',', ':']);
+        let words: Vec<&str> = if in_arguments {
+            let mut words: Vec<&str> = self.channel_names.iter().map(String::as_str).collect();
+            words.extend(["on", "off", "chan", "aux", "group"]);
+            words
+        } else {
+            FUNCTIONS.to_vec()
+        };
+        let candidates = words
+            .into_iter()
+            .filter(|candidate| candidate.to_lowercase().starts_with(&word))
+            .map(String::from)
+            .collect();
+        (start, candidates)
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        Cow::Borrowed(line)
+    }
+}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motu::channel::ChannelType;
+
+    #[test]
+    fn test_candidates() {
+        let mut names = ChannelNames::default();
+        names.insert(ChannelType::Chan, 2, "OT");
+        names.insert(ChannelType::Chan, 10, "Octatrack Cue");
+        names.insert(ChannelType::Aux, 6, "Space");
+        let helper = ShellHelper::new(&names);
+
+        assert_eq!(helper.candidates("v", 1), (0, vec![String::from("vol")]));
+        assert_eq!(
+            helper.candidates("vol(2); mu", 10),
+            (8, vec![String::from("mute")])
+        );
+        assert_eq!(
+            helper.candidates("send(o", 6),
+            (
+                5,
+                vec![
+                    String::from("OT"),
+                    String::from("Octatrack Cue"),
+                    String::from("on"),
+                    String::from("off")
+                ]
+            )
+        );
+        assert_eq!(
+            helper.candidates("vol(Octatrack c", 15),
+            (4, vec![String::from("Octatrack Cue")])
+        );
+        assert_eq!(
+            helper.candidates("send(OT, sp", 11),
+            (9, vec![String::from("Space")])
+        );
+        assert_eq!(
+            helper.candidates("send(OT,sp", 10),
+            (8, vec![String::from("Space")])
+        );
+    }
+
+    #[test]
+    fn test_parse_line_needs_values() {
+        let names = ChannelNames::default();
+        assert_eq!(
+            parse_line("mute(3); vol(2)", &names),
+            Err(String::from(
+                "vol(2) needs a value, e.g. vol(2)=-6dB: mute(3); vol(2)"
+            ))
+        );
+        assert!(parse_line("send(2,4)", &names).is_err());
+        assert_eq!(parse_line("vol(2)=0dB; mute(3)", &names).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,88 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Runs the `motuman` binary with `args`.
+fn motuman(args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_motuman"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+fn example_config() -> String {
+    concat!(env!("CARGO_MANIFEST_DIR"), "/motu_config.toml").to_string()
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("motuman_cli_{}_{}", std::process::id(), name))
+}
+
+#[test]
+fn test_import_print_is_toml() {
+    let config = temp_file("config.toml");
+    fs::copy(example_config(), &config).unwrap();
+    let osc_config = temp_file("osc.json");
+    fs::write(
+        &osc_config,
+        r#"{ "oscConfig": { "control_change": {
+            "100": { "address": "/mix/chan/2/matrix/mute" }
+        } } }"#,
+    )
+    .unwrap();
+
+    let output = motuman(&[
+        "--config",
+        config.to_str().unwrap(),
+        "import",
+        osc_config.to_str().unwrap(),
+        "--print",
+    ]);
+    fs::remove_file(&config).unwrap();
+    fs::remove_file(&osc_config).unwrap();
+
+    let printed: toml::Table = toml::from_str(&String::from_utf8(output.stdout).unwrap()).unwrap();
+    assert_eq!(
+        printed["midi_mapping_cc"]["100"]["mode"].as_str(),
+        Some("toggle")
+    );
+}
+
+#[test]
+fn test_export_is_json() {
+    let output = motuman(&["--config", &example_config(), "export"]);
+    let session: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(session.is_object());
+}
+
+#[test]
+fn test_dry_run_undo_reads_the_journal() {
+    let home = temp_file("home");
+    fs::create_dir_all(&home).unwrap();
+    let journal = home.join(".motuman_journal.json");
+    let json = r#"{"done":[[{"address":"/mix/chan/2/matrix/fader","before":0.5,"after":1.0}]],"undone":[]}"#;
+    fs::write(&journal, json).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_motuman"))
+        .args(["--config", &example_config(), "--dry-run", "undo"])
+        .env("HOME", &home)
+        .output()
+        .unwrap();
+    let kept = fs::read_to_string(&journal).unwrap();
+    fs::remove_dir_all(&home).unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("/mix/chan/2/matrix/fader 0.5"),
+        "{}",
+        stdout
+    );
+    assert_eq!(kept, json);
+}